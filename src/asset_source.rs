@@ -0,0 +1,51 @@
+use std::{fs, io, path::Path, sync::Arc};
+
+use crate::asset_store::AssetError;
+use crate::pack::{AssetPack, PackError, PACK_FILE};
+
+/// Where asset files are read from, the loose `assets/` tree or a packed archive.
+#[derive(Clone)]
+pub enum AssetSource {
+    Disk,
+    Pack(Arc<AssetPack>),
+}
+
+impl AssetSource {
+    /// Uses the pack next to the executable's working directory when one exists.
+    pub fn detect() -> Result<Self, PackError> {
+        if Path::new(PACK_FILE).is_file() {
+            Ok(Self::Pack(Arc::new(AssetPack::open(Path::new(PACK_FILE))?)))
+        } else {
+            Ok(Self::Disk)
+        }
+    }
+
+    pub fn exists(&self, asset_path: &str) -> bool {
+        match self {
+            Self::Disk => Path::new(asset_path).is_file(),
+            Self::Pack(pack) => pack.contains(asset_path),
+        }
+    }
+
+    pub fn read(&self, asset_path: &str) -> Result<Vec<u8>, AssetError> {
+        let data = match self {
+            Self::Disk => fs::read(asset_path).map_err(PackError::from),
+            Self::Pack(pack) => pack.read(asset_path),
+        };
+
+        data.map_err(|err| match err {
+            PackError::NotFound(_) => AssetError::MissingFile(asset_path.to_owned()),
+            PackError::Io(err) if err.kind() == io::ErrorKind::NotFound => {
+                AssetError::MissingFile(asset_path.to_owned())
+            }
+            err => AssetError::Pack(err),
+        })
+    }
+
+    pub fn read_to_string(&self, asset_path: &str) -> Result<String, AssetError> {
+        String::from_utf8(self.read(asset_path)?).map_err(|err| AssetError::Decode {
+            path: asset_path.to_owned(),
+            reason: err.to_string(),
+        })
+    }
+}
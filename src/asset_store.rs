@@ -1,36 +1,703 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::Path,
+    rc::Rc,
+};
 
+use image::RgbaImage;
 use sdl2::{
     image::LoadTexture,
+    mixer::{Chunk, LoaderRWops, Music},
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
     render::{Texture, TextureCreator},
+    rwops::RWops,
+    surface::Surface,
+    ttf::{Font, Sdl2TtfContext},
     video::WindowContext,
 };
+use thiserror::Error;
+
+use crate::asset_source::AssetSource;
+use crate::atlas::{self, AtlasRegion, ATLAS_PAGE_SIZE};
+use crate::hot_reload::same_file;
+use crate::loader::DecodedTexture;
+use crate::manifest::AssetManifest;
+use crate::map::{parse_map, MapError, SharedMap};
+use crate::pack::PackError;
+use crate::sprite_sheet::{parse_sprite_sheet, sprite_sheet_path, SpriteSheet};
+use crate::tiled::parse_tiled_map;
 
 pub type AssetId = String;
 
+pub type AssetGroup = i32;
+
+const MISSING_TEXTURE_SIZE: u32 = 32;
+const MISSING_TEXTURE_CELL: u32 = 8;
+
+#[derive(Debug, Error)]
+pub enum AssetError {
+    #[error("asset file '{0}' does not exist")]
+    MissingFile(String),
+    #[error("failed to decode asset '{path}': {reason}")]
+    Decode { path: String, reason: String },
+    #[error("unknown asset id '{0}'")]
+    UnknownAsset(AssetId),
+    #[error("missing asset files: {}", .0.join(", "))]
+    MissingFiles(Vec<String>),
+    #[error("texture handle {0:?} refers to an unloaded texture")]
+    StaleHandle(TextureHandle),
+    #[error(transparent)]
+    Pack(#[from] PackError),
+    #[error("failed to load map '{path}': {source}")]
+    Map { path: String, source: MapError },
+}
+
+/// Cheap to copy reference to a texture slot in the `AssetStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureHandle(usize);
+
+impl TextureHandle {
+    /// Always resolves to the checkerboard placeholder.
+    pub const MISSING: TextureHandle = TextureHandle(0);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AssetKey {
+    Texture(AssetId),
+    Font(AssetId),
+    Sound(AssetId),
+    Music(AssetId),
+    Tilemap(AssetId),
+}
+
+#[derive(Debug, Default)]
+pub struct AssetUsage {
+    groups: HashMap<AssetKey, HashSet<AssetGroup>>,
+}
+
+impl AssetUsage {
+    pub fn add(&mut self, key: AssetKey, group: AssetGroup) {
+        self.groups.entry(key).or_default().insert(group);
+    }
+
+    /// Drops the group and returns the assets no other group references anymore.
+    pub fn release_group(&mut self, group: AssetGroup) -> Vec<AssetKey> {
+        let mut released = Vec::new();
+        self.groups.retain(|key, groups| {
+            groups.remove(&group);
+            if groups.is_empty() {
+                released.push(key.clone());
+            }
+            !groups.is_empty()
+        });
+        released
+    }
+}
+
+/// The store is owned by the game and shared with the world, so a level
+/// switch never has to move it out of the world's resources.
+pub type SharedAssetStore = Rc<RefCell<AssetStore>>;
+
+enum TextureSlot {
+    Owned(Texture),
+    Packed(AtlasRegion),
+}
+
 pub struct AssetStore {
+    source: AssetSource,
     texture_creator: TextureCreator<WindowContext>,
-    textures: HashMap<String, Rc<Texture>>,
+    ttf: &'static Sdl2TtfContext,
+    textures: Vec<Option<TextureSlot>>,
+    atlas_pages: Vec<Option<Texture>>,
+    texture_handles: HashMap<AssetId, TextureHandle>,
+    sprite_sheets: HashMap<TextureHandle, Rc<SpriteSheet>>,
+    fonts: HashMap<String, Rc<Font<'static, 'static>>>,
+    sounds: HashMap<String, Rc<Chunk>>,
+    music: HashMap<String, Rc<Music<'static>>>,
+    tilemaps: HashMap<String, SharedMap>,
+    paths: HashMap<AssetKey, String>,
+    usage: AssetUsage,
 }
 
 impl AssetStore {
-    pub fn new(texture_creator: TextureCreator<WindowContext>) -> Self {
+    pub fn new(
+        source: AssetSource,
+        texture_creator: TextureCreator<WindowContext>,
+        ttf: &'static Sdl2TtfContext,
+    ) -> Self {
+        let missing_texture = create_missing_texture(&texture_creator);
         Self {
+            source,
             texture_creator,
-            textures: HashMap::new(),
+            ttf,
+            textures: vec![Some(TextureSlot::Owned(missing_texture))],
+            atlas_pages: Vec::new(),
+            texture_handles: HashMap::new(),
+            sprite_sheets: HashMap::new(),
+            fonts: HashMap::new(),
+            sounds: HashMap::new(),
+            music: HashMap::new(),
+            tilemaps: HashMap::new(),
+            paths: HashMap::new(),
+            usage: AssetUsage::default(),
+        }
+    }
+
+    /// Loads everything listed in the manifest into `group`, failing up front if any file is missing.
+    ///
+    /// Assets another group already loaded from the same file are shared instead of reloaded.
+    /// Textures are left out and returned so they can be decoded off the main thread and
+    /// handed back through `add_decoded_textures`.
+    pub fn load_manifest_deferred(
+        &mut self,
+        manifest: &AssetManifest,
+        group: AssetGroup,
+    ) -> Result<Vec<(AssetId, String)>, AssetError> {
+        let missing = manifest.missing_files(&self.source);
+        if !missing.is_empty() {
+            return Err(AssetError::MissingFiles(missing));
+        }
+
+        let mut pending = Vec::new();
+        for (asset_id, asset_path) in &manifest.textures {
+            let key = AssetKey::Texture(asset_id.clone());
+            if self.needs_loading(&key, asset_path) {
+                pending.push((asset_id.clone(), asset_path.clone()));
+            }
+            self.usage.add(key, group);
+        }
+
+        for (asset_id, font) in &manifest.fonts {
+            let key = AssetKey::Font(asset_id.clone());
+            if self.needs_loading(&key, &font.path) {
+                self.add_font(asset_id.clone(), font.path.clone(), font.size)?;
+            }
+            self.usage.add(key, group);
+        }
+
+        for (asset_id, asset_path) in &manifest.sounds {
+            let key = AssetKey::Sound(asset_id.clone());
+            if self.needs_loading(&key, asset_path) {
+                self.add_sound(asset_id.clone(), asset_path.clone())?;
+            }
+            self.usage.add(key, group);
+        }
+
+        for (asset_id, asset_path) in &manifest.music {
+            let key = AssetKey::Music(asset_id.clone());
+            if self.needs_loading(&key, asset_path) {
+                self.add_music(asset_id.clone(), asset_path.clone())?;
+            }
+            self.usage.add(key, group);
+        }
+
+        for (asset_id, asset_path) in &manifest.tilemaps {
+            let key = AssetKey::Tilemap(asset_id.clone());
+            if self.needs_loading(&key, asset_path) {
+                self.add_tilemap(asset_id.clone(), asset_path.clone())?;
+            }
+            self.usage.add(key, group);
+        }
+
+        Ok(pending)
+    }
+
+    /// Unloads the assets only `group` was using, call it after loading the next level's group.
+    pub fn unload_group(&mut self, group: AssetGroup) -> Vec<AssetKey> {
+        let released = self.usage.release_group(group);
+        for key in &released {
+            self.unload(key);
+        }
+        released
+    }
+
+    fn unload(&mut self, key: &AssetKey) {
+        match key {
+            AssetKey::Texture(asset_id) => {
+                if let Some(handle) = self.texture_handles.remove(asset_id) {
+                    self.sprite_sheets.remove(&handle);
+                    let slot = self.textures[handle.0].take();
+                    self.release_slot(slot);
+                }
+            }
+            AssetKey::Font(asset_id) => {
+                self.fonts.remove(asset_id);
+            }
+            AssetKey::Sound(asset_id) => {
+                self.sounds.remove(asset_id);
+            }
+            AssetKey::Music(asset_id) => {
+                self.music.remove(asset_id);
+            }
+            AssetKey::Tilemap(asset_id) => {
+                self.tilemaps.remove(asset_id);
+            }
+        }
+        self.paths.remove(key);
+    }
+
+    fn needs_loading(&self, key: &AssetKey, asset_path: &str) -> bool {
+        self.paths.get(key).map(String::as_str) != Some(asset_path)
+    }
+
+    /// Loads the texture, reusing the existing slot when the id was loaded before so handles stay valid.
+    pub fn add_texture(
+        &mut self,
+        asset_id: AssetId,
+        asset_path: String,
+    ) -> Result<TextureHandle, AssetError> {
+        let data = self.source.read(&asset_path)?;
+        let texture = self
+            .texture_creator
+            .load_texture_bytes(&data)
+            .map_err(|reason| AssetError::Decode {
+                path: asset_path.clone(),
+                reason,
+            })?;
+
+        self.set_texture_slot(asset_id, asset_path, TextureSlot::Owned(texture))
+    }
+
+    /// Uploads decoded images, small ones are packed into atlas pages with one upload per page.
+    pub fn add_decoded_textures(
+        &mut self,
+        textures: Vec<DecodedTexture>,
+    ) -> Result<(), AssetError> {
+        let (packable, standalone): (Vec<_>, Vec<_>) = textures.into_iter().partition(|texture| {
+            let (width, height) = texture.image.dimensions();
+            atlas::can_pack(width, height)
+        });
+
+        for mut texture in standalone {
+            let uploaded = self.upload_image(&mut texture.image, &texture.asset_path)?;
+            self.set_texture_slot(
+                texture.asset_id,
+                texture.asset_path,
+                TextureSlot::Owned(uploaded),
+            )?;
+        }
+
+        self.add_packed_textures(packable)
+    }
+
+    fn add_packed_textures(&mut self, textures: Vec<DecodedTexture>) -> Result<(), AssetError> {
+        let (entries, images): (Vec<_>, Vec<_>) = textures
+            .into_iter()
+            .map(|texture| ((texture.asset_id, texture.asset_path), texture.image))
+            .unzip();
+        let sizes: Vec<(u32, u32)> = images.iter().map(RgbaImage::dimensions).collect();
+
+        let regions = atlas::pack(&sizes, ATLAS_PAGE_SIZE);
+        let pages = atlas::build_pages(&images, &regions, ATLAS_PAGE_SIZE);
+
+        let first_page = self.atlas_pages.len();
+        for mut page in pages {
+            let texture = self.upload_image(&mut page, "texture atlas")?;
+            self.atlas_pages.push(Some(texture));
+        }
+
+        for ((asset_id, asset_path), region) in entries.into_iter().zip(regions) {
+            let region = AtlasRegion {
+                page: first_page + region.page,
+                ..region
+            };
+            self.set_texture_slot(asset_id, asset_path, TextureSlot::Packed(region))?;
+        }
+        Ok(())
+    }
+
+    fn upload_image(&self, image: &mut RgbaImage, asset_path: &str) -> Result<Texture, AssetError> {
+        let (width, height) = image.dimensions();
+        let surface = Surface::from_data(image, width, height, width * 4, PixelFormatEnum::RGBA32)
+            .map_err(|reason| AssetError::Decode {
+                path: asset_path.to_owned(),
+                reason,
+            })?;
+
+        self.texture_creator
+            .create_texture_from_surface(&surface)
+            .map_err(|err| AssetError::Decode {
+                path: asset_path.to_owned(),
+                reason: err.to_string(),
+            })
+    }
+
+    /// Stores the slot under the id, reusing the existing handle so it stays valid across reloads.
+    fn set_texture_slot(
+        &mut self,
+        asset_id: AssetId,
+        asset_path: String,
+        slot: TextureSlot,
+    ) -> Result<TextureHandle, AssetError> {
+        let handle = match self.texture_handles.get(&asset_id) {
+            Some(handle) => *handle,
+            None => {
+                let handle = TextureHandle(self.textures.len());
+                self.textures.push(None);
+                self.texture_handles.insert(asset_id.clone(), handle);
+                handle
+            }
+        };
+
+        let previous = self.textures[handle.0].replace(slot);
+        self.release_slot(previous);
+        self.load_sprite_sheet(handle, &asset_path)?;
+        self.paths.insert(AssetKey::Texture(asset_id), asset_path);
+        Ok(handle)
+    }
+
+    fn load_sprite_sheet(&mut self, handle: TextureHandle, texture_path: &str) -> Result<(), AssetError> {
+        let sheet_path = sprite_sheet_path(texture_path);
+        if !self.source.exists(&sheet_path) {
+            self.sprite_sheets.remove(&handle);
+            return Ok(());
+        }
+
+        let sheet = parse_sprite_sheet(&self.source.read_to_string(&sheet_path)?).map_err(
+            |reason| AssetError::Decode {
+                path: sheet_path,
+                reason,
+            },
+        )?;
+        self.sprite_sheets.insert(handle, Rc::new(sheet));
+        Ok(())
+    }
+
+    pub fn get_sprite_sheet(&self, handle: TextureHandle) -> Option<Rc<SpriteSheet>> {
+        self.sprite_sheets.get(&handle).cloned()
+    }
+
+    /// Destroys an owned texture, or the atlas page once no other slot is packed into it.
+    fn release_slot(&mut self, slot: Option<TextureSlot>) {
+        match slot {
+            Some(TextureSlot::Owned(texture)) => destroy_texture(texture),
+            Some(TextureSlot::Packed(region)) => {
+                let page_in_use = self.textures.iter().flatten().any(
+                    |slot| matches!(slot, TextureSlot::Packed(other) if other.page == region.page),
+                );
+                if !page_in_use {
+                    if let Some(page) = self.atlas_pages[region.page].take() {
+                        destroy_texture(page);
+                    }
+                }
+            }
+            None => {}
         }
     }
 
-    pub fn clear_assets(&mut self) {
-        self.textures.clear();
+    pub fn texture_handle(&self, asset_id: &str) -> Result<TextureHandle, AssetError> {
+        self.texture_handles
+            .get(asset_id)
+            .copied()
+            .ok_or_else(|| AssetError::UnknownAsset(asset_id.to_owned()))
+    }
+
+    /// Resolves the id to a handle, unknown ids get the checkerboard placeholder.
+    pub fn texture_handle_or_missing(&self, asset_id: &str) -> TextureHandle {
+        self.texture_handle(asset_id)
+            .unwrap_or(TextureHandle::MISSING)
+    }
+
+    /// Resolves the texture to draw and where `src`, relative to the original image, lies in it.
+    pub fn get_texture(
+        &self,
+        handle: TextureHandle,
+        src: Rect,
+    ) -> Result<(&Texture, Rect), AssetError> {
+        match self.textures.get(handle.0).and_then(Option::as_ref) {
+            Some(TextureSlot::Owned(texture)) => Ok((texture, src)),
+            Some(TextureSlot::Packed(region)) => self.atlas_pages[region.page]
+                .as_ref()
+                .map(|page| (page, region.resolve(src)))
+                .ok_or(AssetError::StaleHandle(handle)),
+            None => Err(AssetError::StaleHandle(handle)),
+        }
+    }
+
+    /// Returns the requested texture or the whole checkerboard placeholder when the handle was unloaded.
+    pub fn get_texture_or_missing(&self, handle: TextureHandle, src: Rect) -> (&Texture, Rect) {
+        self.get_texture(handle, src).unwrap_or_else(|_| {
+            let missing = Rect::new(0, 0, MISSING_TEXTURE_SIZE, MISSING_TEXTURE_SIZE);
+            self.get_texture(TextureHandle::MISSING, missing).unwrap()
+        })
+    }
+
+    pub fn add_font(
+        &mut self,
+        asset_id: AssetId,
+        asset_path: String,
+        point_size: u16,
+    ) -> Result<(), AssetError> {
+        let font = match &self.source {
+            AssetSource::Disk if Path::new(&asset_path).is_file() => {
+                self.ttf.load_font(&asset_path, point_size)
+            }
+            AssetSource::Disk => return Err(AssetError::MissingFile(asset_path)),
+            AssetSource::Pack(_) => {
+                let data = leak_bytes(self.source.read(&asset_path)?);
+                RWops::from_bytes(data)
+                    .and_then(|rwops| self.ttf.load_font_from_rwops(rwops, point_size))
+            }
+        }
+        .map_err(|reason| AssetError::Decode {
+            path: asset_path.clone(),
+            reason,
+        })?;
+        self.fonts.insert(asset_id.clone(), Rc::new(font));
+        self.paths.insert(AssetKey::Font(asset_id), asset_path);
+        Ok(())
+    }
+
+    pub fn get_font(&self, asset_id: &AssetId) -> Result<Rc<Font<'static, 'static>>, AssetError> {
+        self.fonts
+            .get(asset_id)
+            .cloned()
+            .ok_or_else(|| AssetError::UnknownAsset(asset_id.clone()))
+    }
+
+    pub fn add_sound(&mut self, asset_id: AssetId, asset_path: String) -> Result<(), AssetError> {
+        let data = self.source.read(&asset_path)?;
+        let sound = RWops::from_bytes(&data)
+            .and_then(|rwops| rwops.load_wav())
+            .map_err(|reason| AssetError::Decode {
+                path: asset_path.clone(),
+                reason,
+            })?;
+        self.sounds.insert(asset_id.clone(), Rc::new(sound));
+        self.paths.insert(AssetKey::Sound(asset_id), asset_path);
+        Ok(())
+    }
+
+    pub fn get_sound(&self, asset_id: &AssetId) -> Result<Rc<Chunk>, AssetError> {
+        self.sounds
+            .get(asset_id)
+            .cloned()
+            .ok_or_else(|| AssetError::UnknownAsset(asset_id.clone()))
+    }
+
+    pub fn add_music(&mut self, asset_id: AssetId, asset_path: String) -> Result<(), AssetError> {
+        let music = match &self.source {
+            AssetSource::Disk if Path::new(&asset_path).is_file() => Music::from_file(&asset_path),
+            AssetSource::Disk => return Err(AssetError::MissingFile(asset_path)),
+            AssetSource::Pack(_) => {
+                Music::from_static_bytes(leak_bytes(self.source.read(&asset_path)?))
+            }
+        }
+        .map_err(|reason| AssetError::Decode {
+            path: asset_path.clone(),
+            reason,
+        })?;
+        self.music.insert(asset_id.clone(), Rc::new(music));
+        self.paths.insert(AssetKey::Music(asset_id), asset_path);
+        Ok(())
     }
 
-    pub fn add_texture(&mut self, asset_id: AssetId, asset_path: String) {
-        let texture = self.texture_creator.load_texture(asset_path).unwrap();
-        self.textures.insert(asset_id, Rc::new(texture));
+    pub fn get_music(&self, asset_id: &AssetId) -> Result<Rc<Music<'static>>, AssetError> {
+        self.music
+            .get(asset_id)
+            .cloned()
+            .ok_or_else(|| AssetError::UnknownAsset(asset_id.clone()))
     }
 
-    pub fn get_texture(&self, asset_id: &AssetId) -> Rc<Texture> {
-        self.textures.get(asset_id).unwrap().clone()
+    pub fn add_tilemap(&mut self, asset_id: AssetId, asset_path: String) -> Result<(), AssetError> {
+        let data = self.source.read_to_string(&asset_path)?;
+        let map = if is_tiled_map(&asset_path) {
+            let directory = Path::new(&asset_path).parent().unwrap_or(Path::new(""));
+            parse_tiled_map(&data, |source| {
+                let tileset_path = directory.join(source).to_string_lossy().into_owned();
+                self.source
+                    .read_to_string(&tileset_path)
+                    .map_err(|err| err.to_string())
+            })
+            .map_err(|reason| AssetError::Decode {
+                path: asset_path.clone(),
+                reason,
+            })?
+        } else {
+            parse_map(&data).map_err(|source| AssetError::Map {
+                path: asset_path.clone(),
+                source,
+            })?
+        };
+        self.tilemaps.insert(asset_id.clone(), Rc::new(RefCell::new(map)));
+        self.paths.insert(AssetKey::Tilemap(asset_id), asset_path);
+        Ok(())
+    }
+
+    pub fn get_tilemap(&self, asset_id: &AssetId) -> Result<SharedMap, AssetError> {
+        self.tilemaps
+            .get(asset_id)
+            .cloned()
+            .ok_or_else(|| AssetError::UnknownAsset(asset_id.clone()))
+    }
+
+    /// Reloads every texture and tilemap that was loaded from `path`, or whose sprite sheet it is.
+    pub fn reload_file(&mut self, path: &Path) -> Result<Vec<AssetKey>, AssetError> {
+        let matching: Vec<(AssetKey, String)> = self
+            .paths
+            .iter()
+            .filter(|(key, _)| matches!(key, AssetKey::Texture(_) | AssetKey::Tilemap(_)))
+            .filter(|(key, asset_path)| {
+                same_file(Path::new(asset_path), path)
+                    || matches!(key, AssetKey::Texture(_))
+                        && same_file(Path::new(&sprite_sheet_path(asset_path)), path)
+            })
+            .map(|(key, asset_path)| (key.clone(), asset_path.clone()))
+            .collect();
+
+        for (key, asset_path) in &matching {
+            match key {
+                AssetKey::Texture(asset_id) => {
+                    self.add_texture(asset_id.clone(), asset_path.clone())?;
+                }
+                AssetKey::Tilemap(asset_id) => {
+                    self.add_tilemap(asset_id.clone(), asset_path.clone())?;
+                }
+                _ => {}
+            }
+        }
+        Ok(matching.into_iter().map(|(key, _)| key).collect())
+    }
+}
+
+/// Maps exported from Tiled, everything else uses the comma separated `.map` format.
+fn is_tiled_map(asset_path: &str) -> bool {
+    matches!(
+        Path::new(asset_path).extension().and_then(|ext| ext.to_str()),
+        Some("tmj" | "json")
+    )
+}
+
+/// Fonts and music streamed from a pack keep reading their bytes, so those live for the whole game.
+fn leak_bytes(data: Vec<u8>) -> &'static [u8] {
+    Box::leak(data.into_boxed_slice())
+}
+
+fn destroy_texture(texture: Texture) {
+    // Textures are only reachable through the store, which lives no longer than its renderer
+    unsafe { texture.destroy() };
+}
+
+fn create_missing_texture(texture_creator: &TextureCreator<WindowContext>) -> Texture {
+    let mut surface = Surface::new(
+        MISSING_TEXTURE_SIZE,
+        MISSING_TEXTURE_SIZE,
+        PixelFormatEnum::RGBA8888,
+    )
+    .unwrap();
+
+    let cells = MISSING_TEXTURE_SIZE / MISSING_TEXTURE_CELL;
+    for x in 0..cells {
+        for y in 0..cells {
+            let color = if (x + y) % 2 == 0 {
+                Color::MAGENTA
+            } else {
+                Color::BLACK
+            };
+            let cell = Rect::new(
+                (x * MISSING_TEXTURE_CELL) as i32,
+                (y * MISSING_TEXTURE_CELL) as i32,
+                MISSING_TEXTURE_CELL,
+                MISSING_TEXTURE_CELL,
+            );
+            surface.fill_rect(cell, color).unwrap();
+        }
+    }
+
+    texture_creator
+        .create_texture_from_surface(&surface)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use sdl2::rect::Rect;
+
+    use super::{AssetKey, AssetStore, AssetUsage};
+    use crate::asset_source::AssetSource;
+    use crate::loader::decode_texture;
+    use crate::manifest::AssetManifest;
+
+    #[test]
+    fn releases_only_assets_unused_by_other_groups() {
+        let mut usage = AssetUsage::default();
+        usage.add(AssetKey::Texture("tank".to_owned()), 1);
+        usage.add(AssetKey::Texture("jungle".to_owned()), 1);
+        usage.add(AssetKey::Texture("jungle".to_owned()), 2);
+        usage.add(AssetKey::Sound("helicopter".to_owned()), 2);
+
+        let released = usage.release_group(1);
+        assert_eq!(released, vec![AssetKey::Texture("tank".to_owned())]);
+
+        let mut released = usage.release_group(2);
+        released.sort_by_key(|key| format!("{key:?}"));
+        assert_eq!(
+            released,
+            vec![
+                AssetKey::Sound("helicopter".to_owned()),
+                AssetKey::Texture("jungle".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unloading_a_level_keeps_textures_the_next_level_shares() {
+        let _sdl_lock = crate::sdl::SDL_TEST_LOCK
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        std::env::set_var("SDL_VIDEODRIVER", "dummy");
+        let sdl = sdl2::init().unwrap();
+        let window = sdl
+            .video()
+            .unwrap()
+            .window("test", 16, 16)
+            .hidden()
+            .build()
+            .unwrap();
+        let canvas = window.into_canvas().software().build().unwrap();
+        let ttf = Box::leak(Box::new(sdl2::ttf::init().unwrap()));
+        let mut asset_store = AssetStore::new(AssetSource::Disk, canvas.texture_creator(), ttf);
+
+        let load = |asset_store: &mut AssetStore, textures: &str, group| {
+            let manifest: AssetManifest = toml::from_str(&format!(
+                "map = \"jungle\"\n\n[textures]\n{textures}\n\
+                 [tilemaps]\njungle = \"./assets/tilemaps/jungle.map\"\n"
+            ))
+            .unwrap();
+            let decoded = asset_store
+                .load_manifest_deferred(&manifest, group)
+                .unwrap()
+                .into_iter()
+                .map(|(asset_id, path)| decode_texture(&AssetSource::Disk, asset_id, path))
+                .collect::<Result<_, _>>()
+                .unwrap();
+            asset_store.add_decoded_textures(decoded).unwrap();
+        };
+        load(
+            &mut asset_store,
+            "jungle = \"./assets/tilemaps/jungle.png\"\n\
+             tank = \"./assets/images/tank-tiger-right.png\"",
+            1,
+        );
+        let jungle = asset_store.texture_handle("jungle").unwrap();
+        load(
+            &mut asset_store,
+            "jungle = \"./assets/tilemaps/jungle.png\"\n\
+             truck = \"./assets/images/truck-ford-left.png\"",
+            2,
+        );
+
+        let released = asset_store.unload_group(1);
+
+        assert_eq!(released, vec![AssetKey::Texture("tank".to_owned())]);
+        assert_eq!(asset_store.texture_handle("jungle").unwrap(), jungle);
+        assert!(asset_store
+            .get_texture(jungle, Rect::new(0, 0, 32, 32))
+            .is_ok());
+        assert!(asset_store.texture_handle("tank").is_err());
+        assert!(asset_store.texture_handle("truck").is_ok());
+        assert!(asset_store.get_tilemap(&"jungle".to_owned()).is_ok());
     }
 }
@@ -0,0 +1,154 @@
+use image::{imageops, RgbaImage};
+use sdl2::rect::Rect;
+
+pub const ATLAS_PAGE_SIZE: u32 = 1024;
+/// Images larger than this on either side keep a texture of their own.
+pub const MAX_PACKED_SIZE: u32 = 256;
+/// Transparent gap between packed images so neighbours never bleed into each other.
+const PADDING: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub rect: Rect,
+}
+
+impl AtlasRegion {
+    /// Maps a source rectangle relative to the packed image onto the atlas page.
+    pub fn resolve(&self, src: Rect) -> Rect {
+        let x = src.x().clamp(0, self.rect.width() as i32);
+        let y = src.y().clamp(0, self.rect.height() as i32);
+        let width = src.width().min(self.rect.width() - x as u32);
+        let height = src.height().min(self.rect.height() - y as u32);
+        Rect::new(self.rect.x() + x, self.rect.y() + y, width, height)
+    }
+}
+
+pub fn can_pack(width: u32, height: u32) -> bool {
+    width <= MAX_PACKED_SIZE && height <= MAX_PACKED_SIZE
+}
+
+/// Shelf-packs the sizes onto square pages, tallest first, and returns regions in input order.
+pub fn pack(sizes: &[(u32, u32)], page_size: u32) -> Vec<AtlasRegion> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|a, b| sizes[*b].1.cmp(&sizes[*a].1));
+
+    let mut regions = vec![None; sizes.len()];
+    let (mut page, mut x, mut y, mut shelf_height) = (0, 0, 0, 0);
+
+    for i in order {
+        let (width, height) = sizes[i];
+
+        if x + width > page_size {
+            x = 0;
+            y += shelf_height + PADDING;
+            shelf_height = 0;
+        }
+
+        if y + height > page_size {
+            page += 1;
+            x = 0;
+            y = 0;
+            shelf_height = 0;
+        }
+
+        regions[i] = Some(AtlasRegion {
+            page,
+            rect: Rect::new(x as i32, y as i32, width, height),
+        });
+        x += width + PADDING;
+        shelf_height = shelf_height.max(height);
+    }
+
+    regions.into_iter().flatten().collect()
+}
+
+/// Copies every image into its region, producing one image per atlas page.
+pub fn build_pages(
+    images: &[RgbaImage],
+    regions: &[AtlasRegion],
+    page_size: u32,
+) -> Vec<RgbaImage> {
+    let page_count = regions
+        .iter()
+        .map(|region| region.page + 1)
+        .max()
+        .unwrap_or(0);
+    let mut pages = vec![RgbaImage::new(page_size, page_size); page_count];
+
+    for (image, region) in images.iter().zip(regions) {
+        imageops::replace(
+            &mut pages[region.page],
+            image,
+            region.rect.x() as i64,
+            region.rect.y() as i64,
+        );
+    }
+
+    pages
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+    use sdl2::rect::Rect;
+
+    use super::{build_pages, pack, AtlasRegion};
+
+    #[test]
+    fn packs_without_overlap() {
+        let sizes = [(32, 32), (64, 128), (16, 32), (32, 32), (64, 32), (4, 4)];
+        let regions = pack(&sizes, 128);
+
+        assert_eq!(regions.len(), sizes.len());
+        for (i, a) in regions.iter().enumerate() {
+            assert_eq!((a.rect.width(), a.rect.height()), sizes[i]);
+            assert!(a.rect.right() <= 128 && a.rect.bottom() <= 128);
+
+            for b in regions[i + 1..].iter().filter(|b| b.page == a.page) {
+                assert!(!a.rect.has_intersection(b.rect));
+            }
+        }
+    }
+
+    #[test]
+    fn overflows_onto_new_pages() {
+        let regions = pack(&[(64, 64); 5], 130);
+
+        assert_eq!(regions.iter().map(|region| region.page).max(), Some(1));
+        assert_eq!(regions[4].rect, Rect::new(0, 0, 64, 64));
+    }
+
+    #[test]
+    fn copies_images_into_their_regions() {
+        let red = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        let region = AtlasRegion {
+            page: 0,
+            rect: Rect::new(3, 1, 2, 2),
+        };
+
+        let pages = build_pages(&[red], &[region], 8);
+
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].get_pixel(3, 1), &Rgba([255, 0, 0, 255]));
+        assert_eq!(pages[0].get_pixel(4, 2), &Rgba([255, 0, 0, 255]));
+        assert_eq!(pages[0].get_pixel(2, 1), &Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn resolves_source_rects_inside_region() {
+        let region = AtlasRegion {
+            page: 0,
+            rect: Rect::new(100, 40, 64, 128),
+        };
+
+        assert_eq!(
+            region.resolve(Rect::new(32, 64, 32, 32)),
+            Rect::new(132, 104, 32, 32)
+        );
+        assert_eq!(
+            region.resolve(Rect::new(48, 0, 32, 32)),
+            Rect::new(148, 40, 16, 32)
+        );
+    }
+}
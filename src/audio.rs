@@ -0,0 +1,82 @@
+use sdl2::{
+    mixer::{self, Channel, Chunk, Music, DEFAULT_CHANNELS, DEFAULT_FORMAT, DEFAULT_FREQUENCY},
+    AudioSubsystem, Sdl,
+};
+use secs::query::Query;
+
+use crate::asset_store::{AssetId, SharedAssetStore};
+
+const CHUNK_SIZE: i32 = 1024;
+const MIXING_CHANNELS: i32 = 16;
+
+/// Keeps the SDL audio subsystem and the mixer open for as long as it lives.
+///
+/// Set `SDL_AUDIODRIVER=dummy` before initialising SDL to run without a sound device.
+pub struct Audio {
+    _subsystem: AudioSubsystem,
+}
+
+impl Audio {
+    pub fn new(sdl: &Sdl) -> Result<Self, String> {
+        let subsystem = sdl.audio()?;
+        mixer::open_audio(
+            DEFAULT_FREQUENCY,
+            DEFAULT_FORMAT,
+            DEFAULT_CHANNELS,
+            CHUNK_SIZE,
+        )?;
+        mixer::allocate_channels(MIXING_CHANNELS);
+
+        Ok(Self {
+            _subsystem: subsystem,
+        })
+    }
+}
+
+impl Drop for Audio {
+    fn drop(&mut self) {
+        mixer::close_audio();
+    }
+}
+
+/// Plays a chunk on the first free channel, `loops` of -1 repeats forever.
+pub fn play_chunk(chunk: &Chunk, loops: i32) -> Result<Channel, String> {
+    Channel::all().play(chunk, loops)
+}
+
+pub fn play_music(music: &Music, loops: i32) -> Result<(), String> {
+    music.play(loops)
+}
+
+/// Fire-and-forget sound effect for event handlers, unknown ids and busy channels are ignored.
+pub fn play_one_shot(query: &Query, sound_id: &str) {
+    let asset_store_r = query.resources.get::<SharedAssetStore>().borrow();
+    let asset_store = asset_store_r.get::<SharedAssetStore>().borrow();
+
+    if let Ok(sound) = asset_store.get_sound(&AssetId::from(sound_id)) {
+        let _ = play_chunk(&sound, 0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sdl2::mixer::Chunk;
+
+    use super::{play_chunk, Audio};
+
+    #[test]
+    fn plays_with_dummy_driver() {
+        let _sdl_lock = crate::sdl::SDL_TEST_LOCK
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        std::env::set_var("SDL_AUDIODRIVER", "dummy");
+        let sdl = sdl2::init().unwrap();
+        let _audio = Audio::new(&sdl).unwrap();
+
+        let sound = Chunk::from_file("./assets/sounds/helicopter.wav").unwrap();
+        let channel = play_chunk(&sound, 0).unwrap();
+
+        assert!(channel.is_playing());
+        channel.halt();
+    }
+}
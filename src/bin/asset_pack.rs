@@ -0,0 +1,54 @@
+//! Builds and verifies the asset pack shipped instead of the loose `assets/` tree.
+//!
+//! Run from the directory the game runs from so pack paths match the ones in the manifests:
+//! `cargo run --bin asset_pack -- build ./assets ./assets.pak`
+
+use std::{path::Path, process::ExitCode};
+
+use game2d::pack::{build_pack, AssetPack, PACK_FILE};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let result = match args.as_slice() {
+        ["build", directory] => build(directory, PACK_FILE),
+        ["build", directory, output] => build(directory, output),
+        ["verify"] => verify(PACK_FILE),
+        ["verify", pack_file] => verify(pack_file),
+        _ => Err("usage: asset_pack build <directory> [pack] | verify [pack]".to_owned()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn build(directory: &str, output: &str) -> Result<(), String> {
+    let count =
+        build_pack(Path::new(directory), Path::new(output)).map_err(|err| err.to_string())?;
+    println!("Packed {count} files from {directory} into {output}");
+    verify(output)
+}
+
+fn verify(pack_file: &str) -> Result<(), String> {
+    let pack = AssetPack::open(Path::new(pack_file)).map_err(|err| err.to_string())?;
+    let failures = pack.verify();
+    for failure in &failures {
+        eprintln!("{failure}");
+    }
+
+    if failures.is_empty() {
+        println!("{pack_file}: {} files ok", pack.paths().count());
+        Ok(())
+    } else {
+        Err(format!(
+            "{pack_file}: {} files failed verification",
+            failures.len()
+        ))
+    }
+}
@@ -0,0 +1,63 @@
+//! Writes a generated jungle arena in the native map format.
+//!
+//! `cargo run --bin generate_map -- <seed> <output.map> [columns] [rows]`, point a level's
+//! `tilemaps` entry at the output to play it.
+
+use std::process::ExitCode;
+
+use game2d::generator::MapGeneratorBuilder;
+use game2d::map::save_map;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let result = match args.as_slice() {
+        [seed, output] => generate(seed, output, None, None),
+        [seed, output, columns] => generate(seed, output, Some(columns), None),
+        [seed, output, columns, rows] => generate(seed, output, Some(columns), Some(rows)),
+        _ => Err("usage: generate_map <seed> <output.map> [columns] [rows]".to_owned()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn generate(
+    seed: &str,
+    output: &str,
+    columns: Option<&str>,
+    rows: Option<&str>,
+) -> Result<(), String> {
+    let number = |name: &str, value: &str| {
+        value
+            .parse::<u32>()
+            .map_err(|_| format!("{name} must be a number, got '{value}'"))
+    };
+
+    let mut builder = MapGeneratorBuilder::default();
+    builder.seed(
+        seed.parse()
+            .map_err(|_| format!("seed must be a number, got '{seed}'"))?,
+    );
+    if let Some(columns) = columns {
+        builder.columns(number("columns", columns)?);
+    }
+    if let Some(rows) = rows {
+        builder.rows(number("rows", rows)?);
+    }
+    let generator = builder.build().map_err(|err| err.to_string())?;
+
+    let map = generator.generate();
+    save_map(&map, output).map_err(|err| err.to_string())?;
+    println!(
+        "Generated a {}x{} arena from seed {seed} into {output}",
+        generator.columns, generator.rows
+    );
+    Ok(())
+}
@@ -1,10 +1,13 @@
 use secs::ecs_macro::Component;
 use secs::components::Component;
 use glam::Vec2;
+use sdl2::mixer::MAX_VOLUME;
+use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use time::Duration;
 
-use crate::asset_store::AssetId;
+use crate::asset_store::{AssetId, TextureHandle};
+use crate::map::TileSurface;
 
 #[derive(Debug, Clone, Copy, Component, Builder)]
 pub struct TransformComponent {
@@ -24,11 +27,19 @@ pub struct RigidBodyComponent {
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum SpriteLayer {
-    Tiles(u32),
     Enemies(u32),
     Ui(u32),
 }
 
+impl SpriteLayer {
+    fn rank(&self) -> (u32, u32) {
+        match self {
+            SpriteLayer::Enemies(order) => (0, *order),
+            SpriteLayer::Ui(order) => (1, *order),
+        }
+    }
+}
+
 impl PartialOrd for SpriteLayer {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -37,17 +48,7 @@ impl PartialOrd for SpriteLayer {
 
 impl Ord for SpriteLayer {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        match (self, other) {
-            (SpriteLayer::Tiles(a), SpriteLayer::Tiles(b)) => a.cmp(&b),
-            (SpriteLayer::Tiles(_), SpriteLayer::Enemies(_)) => std::cmp::Ordering::Less,
-            (SpriteLayer::Tiles(_), SpriteLayer::Ui(_)) => std::cmp::Ordering::Less,
-            (SpriteLayer::Enemies(_), SpriteLayer::Tiles(_)) => std::cmp::Ordering::Greater,
-            (SpriteLayer::Enemies(a), SpriteLayer::Enemies(b)) => a.cmp(&b),
-            (SpriteLayer::Enemies(_), SpriteLayer::Ui(_)) => std::cmp::Ordering::Less,
-            (SpriteLayer::Ui(_), SpriteLayer::Tiles(_)) => std::cmp::Ordering::Greater,
-            (SpriteLayer::Ui(_), SpriteLayer::Enemies(_)) => std::cmp::Ordering::Greater,
-            (SpriteLayer::Ui(a), SpriteLayer::Ui(b)) => a.cmp(&b)
-        }
+        self.rank().cmp(&other.rank())
     }
 }
 
@@ -55,38 +56,54 @@ impl Ord for SpriteLayer {
 pub struct SpriteComponent {
     pub width: u32,
     pub height: u32,
-    pub asset_id: AssetId,
+    pub texture: TextureHandle,
     pub src: Rect,
     pub layer: SpriteLayer
 }
 
 impl SpriteComponent {
-    pub fn tile(width: u32, height: u32, asset_id: &str) -> Self {
-        Self { width, height, asset_id: asset_id.to_owned(), src: Rect::new(0, 0, width, height), layer: SpriteLayer::Tiles(1) }
-    }
-    
-    pub fn enemy(width: u32, height: u32, asset_id: &str) -> Self {
-        Self { width, height, asset_id: asset_id.to_owned(), src: Rect::new(0, 0, width, height), layer: SpriteLayer::Enemies(1) }
+    pub fn enemy(width: u32, height: u32, texture: TextureHandle) -> Self {
+        Self { width, height, texture, src: Rect::new(0, 0, width, height), layer: SpriteLayer::Enemies(1) }
     }
 
-    pub fn ui(width: u32, height: u32, asset_id: &str) -> Self {
-        Self { width, height, asset_id: asset_id.to_owned(), src: Rect::new(0, 0, width, height), layer: SpriteLayer::Ui(1) }
+    pub fn ui(width: u32, height: u32, texture: TextureHandle) -> Self {
+        Self { width, height, texture, src: Rect::new(0, 0, width, height), layer: SpriteLayer::Ui(1) }
     }
 }
 
+#[derive(Debug, Clone, Component)]
+pub struct TextLabelComponent {
+    pub text: String,
+    pub font_id: AssetId,
+    pub color: Color,
+    pub layer: SpriteLayer
+}
+
+impl TextLabelComponent {
+    pub fn ui(text: &str, font_id: &str, color: Color) -> Self {
+        Self { text: text.to_owned(), font_id: font_id.to_owned(), color, layer: SpriteLayer::Ui(2) }
+    }
+}
 
+/// Plays a named clip from the sprite sheet of the entity's texture.
 #[derive(Debug, Clone, Component, Builder)]
 pub struct AnimationComponent {
-    pub num_of_frames: u32,
-    #[builder(default = "1")]
-    pub current_frame: u32,
-    #[builder(default = "1")]
-    pub frame_rate_speed: u32,
-    #[builder(default = "true")]
-    pub should_loop: bool,
+    #[builder(setter(into))]
+    pub clip: String,
+    #[builder(default = "0")]
+    pub current_frame: usize,
     pub start_time: Duration,
 }
 
+impl AnimationComponent {
+    /// Switches clips without restarting the timeline, so the animation keeps its rhythm.
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            self.clip = clip.to_owned();
+        }
+    }
+}
+
 #[derive(Debug, Clone, Component, Builder)]
 pub struct BoxColliderComponent {
     pub width: u32,
@@ -109,3 +126,136 @@ pub struct KeyboardControlledComponent {
 
 #[derive(Debug, Clone, Component)]
 pub struct CameraFollowComponent;
+
+#[derive(Debug, Clone, Component, Builder)]
+pub struct AudioSourceComponent {
+    pub sound_id: AssetId,
+    #[builder(default = "true")]
+    pub looping: bool,
+    #[builder(default = "MAX_VOLUME")]
+    pub volume: i32,
+}
+
+/// Marks entities spawned from the level tilemap.
+#[derive(Debug, Clone, Component)]
+pub struct TileComponent;
+
+/// Collider merged from map tiles, blocks or slows entities with a `RigidBodyComponent`.
+#[derive(Debug, Clone, Component)]
+pub struct StaticColliderComponent {
+    pub surface: TileSurface,
+}
+
+/// Drives the entity along an A* path to `goal` at `speed`, planning again when it gets stuck.
+#[derive(Debug, Clone, Component, Builder)]
+pub struct PathFollowComponent {
+    /// Where to go, in scaled map pixels.
+    pub goal: Vec2,
+    #[builder(default = "50.0")]
+    pub speed: f32,
+    /// Waypoints left to reach, the last one is the goal.
+    #[builder(default)]
+    pub path: Vec<Vec2>,
+    /// Whether `path` was planned, reset it to plan again after moving the goal.
+    #[builder(default)]
+    pub planned: bool,
+    /// Seconds the entity hasn't made progress towards its goal.
+    #[builder(default = "0.0")]
+    pub stuck_time: f32,
+    #[builder(default)]
+    pub last_position: Option<Vec2>,
+}
+
+/// Side an entity fights on, damage only hurts entities of the other side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Faction {
+    Player,
+    Enemy,
+}
+
+/// Hit points of an entity that can be destroyed, `HealthSystem` removes it once they run out.
+#[derive(Debug, Clone, Component)]
+pub struct HealthComponent {
+    pub hit_points: u32,
+    pub max_hit_points: u32,
+    pub faction: Faction,
+    /// Seconds after a hit during which the entity takes no further damage.
+    pub invulnerability: f32,
+    /// Seconds of invulnerability left.
+    pub invulnerable_for: f32,
+}
+
+impl HealthComponent {
+    pub fn new(hit_points: u32, faction: Faction) -> Self {
+        Self {
+            hit_points,
+            max_hit_points: hit_points,
+            faction,
+            invulnerability: 1.0,
+            invulnerable_for: 0.0,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.hit_points == 0
+    }
+
+    /// Takes a hit unless still invulnerable from the last one, returns whether it landed.
+    pub fn take_damage(&mut self, amount: u32) -> bool {
+        if self.is_dead() || self.invulnerable_for > 0.0 {
+            return false;
+        }
+        self.hit_points = self.hit_points.saturating_sub(amount);
+        self.invulnerable_for = self.invulnerability;
+        true
+    }
+}
+
+/// Damage dealt to entities of the other faction on collision, by hazards and projectiles.
+#[derive(Debug, Clone, Component, Builder)]
+pub struct DamageComponent {
+    pub amount: u32,
+    pub faction: Faction,
+    /// Removes the entity once it hits something of the other faction, like a bullet.
+    #[builder(default = "false")]
+    pub destroy_on_hit: bool,
+}
+
+/// Flies over water and slow tiles, only solid tiles stop it.
+#[derive(Debug, Clone, Component)]
+pub struct AirborneComponent;
+
+/// Fires bullets along `direction`, on its own for turrets or when triggered by the player.
+#[derive(Debug, Clone, Component, Builder)]
+pub struct ProjectileEmitterComponent {
+    pub direction: Vec2,
+    pub sprite: TextureHandle,
+    pub faction: Faction,
+    /// Bullet speed, in pixels per second.
+    #[builder(default = "300.0")]
+    pub speed: f32,
+    /// Shots per second.
+    #[builder(default = "1.0")]
+    pub fire_rate: f32,
+    /// Seconds a bullet flies before it disappears.
+    #[builder(default = "2.0")]
+    pub lifetime: f32,
+    #[builder(default = "10")]
+    pub damage: u32,
+    /// Fires whenever the cooldown allows instead of waiting for `triggered`.
+    #[builder(default = "true")]
+    pub automatic: bool,
+    /// Fires the next shot the cooldown allows.
+    #[builder(default = "false")]
+    pub triggered: bool,
+    /// Seconds until the next shot.
+    #[builder(default = "0.0")]
+    pub cooldown: f32,
+}
+
+/// Bullet fired by a `ProjectileEmitterComponent`, removed once its lifetime runs out or it
+/// hits a solid tile.
+#[derive(Debug, Clone, Component)]
+pub struct ProjectileComponent {
+    pub lifetime: f32,
+}
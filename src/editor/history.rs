@@ -0,0 +1,157 @@
+use crate::map::Map;
+
+/// Tile changed in one cell of a layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TileEdit {
+    layer: usize,
+    cell: usize,
+    before: u32,
+    after: u32,
+}
+
+/// Undo and redo of tile painting, everything painted while the mouse is held down is one step.
+#[derive(Debug, Default)]
+pub struct EditHistory {
+    undo: Vec<Vec<TileEdit>>,
+    redo: Vec<Vec<TileEdit>>,
+    stroke: Vec<TileEdit>,
+}
+
+impl EditHistory {
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.stroke.clear();
+    }
+
+    /// Sets the cell to `tile` as part of the current stroke, returns whether the map changed.
+    pub fn paint(&mut self, map: &mut Map, layer: usize, cell: usize, tile: u32) -> bool {
+        let Some(current) = map
+            .layers
+            .get_mut(layer)
+            .and_then(|layer| layer.tiles.get_mut(cell))
+        else {
+            return false;
+        };
+        if *current == tile {
+            return false;
+        }
+
+        match self
+            .stroke
+            .iter_mut()
+            .find(|edit| edit.layer == layer && edit.cell == cell)
+        {
+            Some(edit) => edit.after = tile,
+            None => self.stroke.push(TileEdit {
+                layer,
+                cell,
+                before: *current,
+                after: tile,
+            }),
+        }
+        *current = tile;
+        true
+    }
+
+    /// Closes the current stroke so the next paint starts a new undo step.
+    pub fn end_stroke(&mut self) {
+        if self.stroke.is_empty() {
+            return;
+        }
+        self.undo.push(std::mem::take(&mut self.stroke));
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self, map: &mut Map) -> bool {
+        self.end_stroke();
+        let Some(stroke) = self.undo.pop() else {
+            return false;
+        };
+        for edit in stroke.iter().rev() {
+            set_tile(map, edit, edit.before);
+        }
+        self.redo.push(stroke);
+        true
+    }
+
+    pub fn redo(&mut self, map: &mut Map) -> bool {
+        self.end_stroke();
+        let Some(stroke) = self.redo.pop() else {
+            return false;
+        };
+        for edit in &stroke {
+            set_tile(map, edit, edit.after);
+        }
+        self.undo.push(stroke);
+        true
+    }
+}
+
+/// Edits of cells the map no longer has, after it was reloaded from disk, are skipped.
+fn set_tile(map: &mut Map, edit: &TileEdit, tile: u32) {
+    if let Some(current) = map
+        .layers
+        .get_mut(edit.layer)
+        .and_then(|layer| layer.tiles.get_mut(edit.cell))
+    {
+        *current = tile;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EditHistory;
+    use crate::map::test_map;
+
+    #[test]
+    fn undoes_and_redoes_strokes() {
+        let mut map = test_map("", "[ground]\n0,0\n0,0\n[overhang]\n-1,-1\n-1,-1\n");
+        let mut history = EditHistory::default();
+
+        assert!(history.paint(&mut map, 0, 0, 1));
+        assert!(history.paint(&mut map, 0, 0, 2));
+        assert!(history.paint(&mut map, 0, 1, 3));
+        assert!(!history.paint(&mut map, 0, 1, 3));
+        history.end_stroke();
+        assert!(history.paint(&mut map, 1, 3, 1));
+        history.end_stroke();
+        assert_eq!(map.layers[0].tiles, vec![2, 3, 0, 0]);
+
+        assert!(history.undo(&mut map));
+        assert_eq!(map.layers[1].tiles[3], u32::MAX);
+        assert!(history.undo(&mut map));
+        assert_eq!(map.layers[0].tiles, vec![0, 0, 0, 0]);
+        assert!(!history.undo(&mut map));
+
+        assert!(history.redo(&mut map));
+        assert_eq!(map.layers[0].tiles, vec![2, 3, 0, 0]);
+
+        history.paint(&mut map, 0, 2, 1);
+        history.end_stroke();
+        assert!(!history.can_redo());
+        assert!(!history.paint(&mut map, 2, 0, 1));
+    }
+
+    #[test]
+    fn skips_cells_missing_after_a_reload() {
+        let mut map = test_map("", "[ground]\n0,0\n0,0\n[overhang]\n-1,-1\n-1,-1\n");
+        let mut history = EditHistory::default();
+        history.paint(&mut map, 1, 3, 1);
+        history.paint(&mut map, 0, 0, 2);
+        history.end_stroke();
+
+        let mut reloaded = test_map("", "0\n");
+        assert!(history.undo(&mut reloaded));
+        assert!(history.redo(&mut reloaded));
+        assert_eq!(reloaded.layers[0].tiles, vec![2]);
+    }
+}
@@ -0,0 +1,279 @@
+//! Tilemap editor drawn with imgui on top of the running level, toggled with `E` in dev mode.
+
+use std::path::Path;
+
+use imgui::{Condition, Key, MouseButton};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton as SdlMouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{TextureCreator, WindowCanvas};
+use sdl2::video::WindowContext;
+use time::Duration;
+
+use crate::asset_store::AssetStore;
+use crate::game::Camera;
+use crate::logger::Logger;
+use crate::map::{save_map, Map, EMPTY_TILE};
+use crate::resources::Tilemap;
+
+use self::history::EditHistory;
+use self::renderer::{texture_id, ImguiRenderer};
+
+mod history;
+mod renderer;
+
+/// Size of a tile button in the palette, in pixels.
+const PALETTE_TILE_SIZE: f32 = 32.0;
+
+/// Paints tiles of the palette into the live map under the mouse and saves it back to its file.
+///
+/// Left click paints the selected tile, right click erases. Saving a map that was imported
+/// from Tiled writes a `.map` file next to it.
+pub struct TilemapEditor {
+    imgui: imgui::Context,
+    renderer: ImguiRenderer,
+    map_file: Option<String>,
+    history: EditHistory,
+    selected_tile: u32,
+    layer: usize,
+    unsaved: bool,
+}
+
+impl TilemapEditor {
+    pub fn new(texture_creator: TextureCreator<WindowContext>) -> Result<Self, String> {
+        let mut imgui = imgui::Context::create();
+        imgui.set_ini_filename(None);
+        let renderer = ImguiRenderer::new(&mut imgui, texture_creator)?;
+
+        Ok(Self {
+            imgui,
+            renderer,
+            map_file: None,
+            history: EditHistory::default(),
+            selected_tile: 0,
+            layer: 0,
+            unsaved: false,
+        })
+    }
+
+    /// Starts editing the map of a newly loaded level.
+    pub fn open(&mut self, map_file: Option<String>) {
+        self.map_file = map_file;
+        self.history.clear();
+        self.layer = 0;
+        self.unsaved = false;
+    }
+
+    /// Forwards mouse and shortcut keys to imgui.
+    pub fn handle_event(&mut self, event: &Event) {
+        let io = self.imgui.io_mut();
+        match event {
+            Event::MouseMotion { x, y, .. } => io.add_mouse_pos_event([*x as f32, *y as f32]),
+            Event::MouseButtonDown { mouse_btn, .. } | Event::MouseButtonUp { mouse_btn, .. } => {
+                let down = matches!(event, Event::MouseButtonDown { .. });
+                match mouse_btn {
+                    SdlMouseButton::Left => io.add_mouse_button_event(MouseButton::Left, down),
+                    SdlMouseButton::Right => io.add_mouse_button_event(MouseButton::Right, down),
+                    _ => {}
+                }
+            }
+            Event::MouseWheel { x, y, .. } => io.add_mouse_wheel_event([*x as f32, *y as f32]),
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            }
+            | Event::KeyUp {
+                keycode: Some(keycode),
+                ..
+            } => {
+                let down = matches!(event, Event::KeyDown { .. });
+                let key = match keycode {
+                    Keycode::LCtrl | Keycode::RCtrl => Key::ModCtrl,
+                    Keycode::LShift | Keycode::RShift => Key::ModShift,
+                    Keycode::Z => Key::Z,
+                    Keycode::Y => Key::Y,
+                    Keycode::S => Key::S,
+                    _ => return,
+                };
+                io.add_key_event(key, down);
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs one editor frame, painting into the level map and drawing the editor over it.
+    ///
+    /// Returns whether a tile of the map changed.
+    pub fn frame(
+        &mut self,
+        canvas: &mut WindowCanvas,
+        delta_time: Duration,
+        camera: &Camera,
+        asset_store: &AssetStore,
+        tilemap: &Tilemap,
+        logger: &mut Logger,
+    ) -> bool {
+        let (width, height) = canvas.output_size().unwrap_or((0, 0));
+        let io = self.imgui.io_mut();
+        io.display_size = [width as f32, height as f32];
+        io.delta_time = delta_time.as_seconds_f32().max(f32::EPSILON);
+
+        let ui = self.imgui.new_frame();
+        let mut map = tilemap.map.borrow_mut();
+        let mut edited = false;
+        self.layer = self.layer.min(map.layers.len().saturating_sub(1));
+
+        let [mouse_x, mouse_y] = ui.io().mouse_pos;
+        let tile_size = map.scaled_tile_size();
+        let (column, row) = (
+            ((mouse_x + camera.rect.x as f32) / tile_size).floor(),
+            ((mouse_y + camera.rect.y as f32) / tile_size).floor(),
+        );
+        let hovered = (!ui.io().want_capture_mouse
+            && column >= 0.0
+            && row >= 0.0
+            && (column as u32) < map.tiles_per_row
+            && (row as u32) < map.rows())
+        .then_some((column as u32, row as u32));
+
+        if let Some((column, row)) = hovered {
+            let cell = (row * map.tiles_per_row + column) as usize;
+            if ui.is_mouse_down(MouseButton::Left) {
+                edited |= self
+                    .history
+                    .paint(&mut map, self.layer, cell, self.selected_tile);
+            } else if ui.is_mouse_down(MouseButton::Right) {
+                edited |= self.history.paint(&mut map, self.layer, cell, EMPTY_TILE);
+            }
+        }
+        if !ui.is_mouse_down(MouseButton::Left) && !ui.is_mouse_down(MouseButton::Right) {
+            self.history.end_stroke();
+        }
+
+        let ctrl = ui.io().key_ctrl;
+        let mut undo = ctrl && ui.is_key_pressed(Key::Z) && !ui.io().key_shift;
+        let mut redo =
+            ctrl && (ui.is_key_pressed(Key::Y) || (ui.is_key_pressed(Key::Z) && ui.io().key_shift));
+        let mut save = ctrl && ui.is_key_pressed(Key::S);
+
+        let (tileset, _) =
+            asset_store.get_texture_or_missing(tilemap.tileset, Rect::new(0, 0, 1, 1));
+        let tileset_size = tileset.query();
+        let tileset_id = texture_id(tileset);
+
+        ui.window("Tilemap editor")
+            .position([10.0, 10.0], Condition::FirstUseEver)
+            .always_auto_resize(true)
+            .build(|| {
+                let file = self.map_file.as_deref().unwrap_or("no map file");
+                let marker = if self.unsaved { " *" } else { "" };
+                ui.text(format!("{file}{marker}"));
+                match hovered {
+                    Some((column, row)) => ui.text(format!("Cell {column}, {row}")),
+                    None => ui.text("Cell -"),
+                }
+
+                ui.separator();
+                for (i, layer) in map.layers.iter().enumerate() {
+                    if ui.radio_button_bool(format!("{i}: {:?}", layer.kind), self.layer == i) {
+                        self.layer = i;
+                    }
+                }
+
+                ui.separator();
+                ui.disabled(!self.history.can_undo(), || undo |= ui.button("Undo"));
+                ui.same_line();
+                ui.disabled(!self.history.can_redo(), || redo |= ui.button("Redo"));
+                ui.same_line();
+                ui.disabled(self.map_file.is_none(), || save |= ui.button("Save"));
+
+                ui.separator();
+                for tile in 0..map.tile_count() {
+                    let src = Rect::new(
+                        (tile % map.tiles_per_file_row * map.tile_size) as i32,
+                        (tile / map.tiles_per_file_row * map.tile_size) as i32,
+                        map.tile_size,
+                        map.tile_size,
+                    );
+                    let (_, src) = asset_store.get_texture_or_missing(tilemap.tileset, src);
+                    let (texture_width, texture_height) =
+                        (tileset_size.width as f32, tileset_size.height as f32);
+                    let background = if tile == self.selected_tile {
+                        [1.0, 0.8, 0.0, 1.0]
+                    } else {
+                        [0.0, 0.0, 0.0, 0.0]
+                    };
+
+                    let clicked = ui
+                        .image_button_config(
+                            format!("tile {tile}"),
+                            tileset_id,
+                            [PALETTE_TILE_SIZE, PALETTE_TILE_SIZE],
+                        )
+                        .uv0([
+                            src.x() as f32 / texture_width,
+                            src.y() as f32 / texture_height,
+                        ])
+                        .uv1([
+                            src.right() as f32 / texture_width,
+                            src.bottom() as f32 / texture_height,
+                        ])
+                        .background_col(background)
+                        .build();
+                    if clicked {
+                        self.selected_tile = tile;
+                    }
+                    if (tile + 1) % map.tiles_per_file_row != 0 {
+                        ui.same_line();
+                    }
+                }
+            });
+
+        if undo {
+            edited |= self.history.undo(&mut map);
+        }
+        if redo {
+            edited |= self.history.redo(&mut map);
+        }
+        self.unsaved |= edited;
+        if save {
+            self.save(&map, logger);
+        }
+
+        if let Some((column, row)) = hovered {
+            let cell_rect = Rect::new(
+                (column as f32 * tile_size) as i32 - camera.rect.x,
+                (row as f32 * tile_size) as i32 - camera.rect.y,
+                tile_size as u32,
+                tile_size as u32,
+            );
+            canvas.set_draw_color(Color::YELLOW);
+            canvas.draw_rect(cell_rect).unwrap();
+        }
+
+        let draw_data = self.imgui.render();
+        if let Err(err) = self.renderer.render(canvas, draw_data) {
+            logger.error(&format!("Failed to draw the editor: {err}"));
+        }
+        edited
+    }
+
+    /// Writes the map back to its file, next to it for maps imported from Tiled.
+    fn save(&mut self, map: &Map, logger: &mut Logger) {
+        let Some(map_file) = &self.map_file else {
+            return;
+        };
+        let path = Path::new(map_file).with_extension("map");
+        let path = path.to_string_lossy();
+
+        match save_map(map, &path) {
+            Ok(()) => {
+                logger.info(&format!("Saved map to {path}"));
+                self.unsaved = false;
+            }
+            Err(err) => logger.error(&format!("Failed to save map to {path}: {err}")),
+        }
+    }
+}
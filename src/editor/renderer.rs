@@ -0,0 +1,120 @@
+use std::ffi::c_void;
+use std::mem::size_of;
+
+use imgui::{DrawCmd, DrawCmdParams, DrawData, DrawIdx, DrawVert, TextureId};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Texture, TextureCreator, WindowCanvas};
+use sdl2::sys::{SDL_Color, SDL_RenderGeometryRaw, SDL_Texture};
+use sdl2::video::WindowContext;
+
+/// Draws imgui windows with the game's SDL renderer.
+///
+/// Texture ids are raw `SDL_Texture` pointers, so game textures like the tileset can be shown as is.
+pub struct ImguiRenderer {
+    /// Keeps the SDL renderer alive until the font texture is destroyed.
+    _texture_creator: TextureCreator<WindowContext>,
+    font_texture: Option<Texture>,
+}
+
+impl ImguiRenderer {
+    pub fn new(
+        imgui: &mut imgui::Context,
+        texture_creator: TextureCreator<WindowContext>,
+    ) -> Result<Self, String> {
+        let fonts = imgui.fonts();
+        let atlas = fonts.build_rgba32_texture();
+        let mut font_texture = texture_creator
+            .create_texture_static(PixelFormatEnum::RGBA32, atlas.width, atlas.height)
+            .map_err(|err| err.to_string())?;
+        font_texture
+            .update(None, atlas.data, atlas.width as usize * 4)
+            .map_err(|err| err.to_string())?;
+        font_texture.set_blend_mode(BlendMode::Blend);
+        fonts.tex_id = texture_id(&font_texture);
+
+        Ok(Self {
+            _texture_creator: texture_creator,
+            font_texture: Some(font_texture),
+        })
+    }
+
+    pub fn render(&self, canvas: &mut WindowCanvas, draw_data: &DrawData) -> Result<(), String> {
+        let [offset_x, offset_y] = draw_data.display_pos;
+
+        for draw_list in draw_data.draw_lists() {
+            let vertices = draw_list.vtx_buffer();
+            let indices = draw_list.idx_buffer();
+
+            for command in draw_list.commands() {
+                let DrawCmd::Elements {
+                    count,
+                    cmd_params:
+                        DrawCmdParams {
+                            clip_rect: [left, top, right, bottom],
+                            texture_id,
+                            vtx_offset,
+                            idx_offset,
+                        },
+                } = command
+                else {
+                    continue;
+                };
+
+                let (left, top) = (left - offset_x, top - offset_y);
+                let (right, bottom) = (right - offset_x, bottom - offset_y);
+                if right <= left || bottom <= top {
+                    continue;
+                }
+                canvas.set_clip_rect(Rect::new(
+                    left as i32,
+                    top as i32,
+                    (right - left) as u32,
+                    (bottom - top) as u32,
+                ));
+
+                let vertices = &vertices[vtx_offset..];
+                let indices = &indices[idx_offset..idx_offset + count];
+                let stride = size_of::<DrawVert>() as i32;
+                let first = vertices.as_ptr();
+                // Positions, uvs and colors are read in place from imgui's interleaved vertices
+                let result = unsafe {
+                    SDL_RenderGeometryRaw(
+                        canvas.raw(),
+                        texture_id.id() as *mut SDL_Texture,
+                        (*first).pos.as_ptr(),
+                        stride,
+                        (*first).col.as_ptr() as *const SDL_Color,
+                        stride,
+                        (*first).uv.as_ptr(),
+                        stride,
+                        vertices.len() as i32,
+                        indices.as_ptr() as *const c_void,
+                        indices.len() as i32,
+                        size_of::<DrawIdx>() as i32,
+                    )
+                };
+                if result != 0 {
+                    canvas.set_clip_rect(None);
+                    return Err(sdl2::get_error());
+                }
+            }
+        }
+
+        canvas.set_clip_rect(None);
+        Ok(())
+    }
+}
+
+impl Drop for ImguiRenderer {
+    fn drop(&mut self) {
+        if let Some(texture) = self.font_texture.take() {
+            // The renderer that created it lives as long as `_texture_creator`
+            unsafe { texture.destroy() };
+        }
+    }
+}
+
+pub fn texture_id(texture: &Texture) -> TextureId {
+    TextureId::from(texture.raw())
+}
@@ -1,3 +1,5 @@
+use std::{cell::RefCell, rc::Rc};
+
 use glam::Vec2;
 use sdl2::rect::Rect;
 use sdl2::render::TextureCreator;
@@ -6,21 +8,28 @@ use sdl2::{event::Event, keyboard::Keycode, pixels::Color, EventPump};
 use secs::SystemBuilder;
 use time::Duration;
 
-use crate::asset_store::AssetStore;
+use crate::asset_source::AssetSource;
+use crate::asset_store::{AssetStore, SharedAssetStore, TextureHandle};
+use crate::audio::play_music;
+use crate::editor::TilemapEditor;
 use crate::components::{
-    AnimationComponent, AnimationComponentBuilder, BoxColliderComponent,
-    BoxColliderComponentBuilder, CameraFollowComponent, KeyboardControlledComponentBuilder, RigidBodyComponent, SpriteComponent, TransformComponent,
+    AirborneComponent, AnimationComponent, AnimationComponentBuilder, AudioSourceComponent,
+    AudioSourceComponentBuilder, BoxColliderComponent,
+    BoxColliderComponentBuilder, CameraFollowComponent, DamageComponentBuilder, Faction, HealthComponent, KeyboardControlledComponentBuilder, PathFollowComponent, PathFollowComponentBuilder, ProjectileComponent, ProjectileEmitterComponent, ProjectileEmitterComponentBuilder, RigidBodyComponent, SpriteComponent, StaticColliderComponent, TextLabelComponent, TileComponent, TransformComponent,
     TransformComponentBuilder,
 };
+use crate::loader::{DecodedTexture, TextureLoader};
 use crate::logger::Logger;
-use crate::map::load_map;
-use crate::resources::DeltaTime;
+use crate::manifest::{load_manifest, AssetManifest};
+use crate::map::{Map, SharedMap, SpawnPoint};
+use crate::pathfinding::NavGrid;
+use crate::resources::{DeltaTime, Tilemap};
 use crate::sdl::{Context, MILLIS_PER_FRAME};
-use crate::systems::events::KeyPressed;
+use crate::systems::events::{KeyPressed, LevelUnloaded};
 use crate::systems::{
-    collision_event_handler, key_pressed_hanlder,
-    AnimationSystem, CameraMovementSystem, CollisionSystem, DebugSystem, MovementSystem,
-    RenderSystem,
+    collision_event_handler, entity_died_handler, key_pressed_hanlder, level_unloaded_handler,
+    AnimationSystem, AudioSystem, CameraMovementSystem, CollisionSystem, DebugSystem, HealthSystem, HotReloadSystem, LoadingScreenSystem, MovementSystem,
+    PathFollowSystem, ProjectileEmitterSystem, ProjectileSystem, ReloadedMap, RenderSystem, TilemapEditorSystem,
 };
 use secs::events::WorldEventSubscriber;
 use secs::world::World;
@@ -30,20 +39,65 @@ pub const WINDOW_HEIGHT: u32 = 600;
 
 pub struct Game<'a> {
     is_running: bool,
+    dev_mode: bool,
+    source: AssetSource,
     context: Context,
     logger: Logger,
     world: World<'a>,
+    asset_store: SharedAssetStore,
+    level: Option<i32>,
+    reloaded_map: ReloadedMap,
+    loading: Option<LevelLoading>,
+    /// Only available in dev mode, where saved maps are hot reloaded.
+    editor: Option<Rc<RefCell<TilemapEditor>>>,
+}
+
+struct LevelLoading {
+    level: i32,
+    manifest: AssetManifest,
+    loader: TextureLoader,
+    decoded: Vec<DecodedTexture>,
 }
 
 impl Game<'static> {
-    pub fn new() -> Self {
+    pub fn new(dev_mode: bool) -> Self {
         let context = Context::new("My game", WINDOW_WIDTH, WINDOW_HEIGHT);
+        let mut logger = Logger::new();
+
+        // Dev mode edits the loose files, so hot reload always reads from disk
+        let source = if dev_mode {
+            AssetSource::Disk
+        } else {
+            AssetSource::detect().unwrap_or_else(|err| {
+                logger.error(&format!("Ignoring unreadable asset pack: {err}"));
+                AssetSource::Disk
+            })
+        };
+        let texture_creator: TextureCreator<WindowContext> =
+            context.canvas.borrow().texture_creator();
+        let asset_store = AssetStore::new(source.clone(), texture_creator, context.ttf);
+
+        let editor = if dev_mode {
+            TilemapEditor::new(context.canvas.borrow().texture_creator())
+                .map_err(|err| logger.error(&format!("Tilemap editor unavailable: {err}")))
+                .ok()
+                .map(|editor| Rc::new(RefCell::new(editor)))
+        } else {
+            None
+        };
 
         Self {
             context,
             is_running: true,
-            logger: Logger::new(),
+            dev_mode,
+            source,
+            logger,
             world: World::new(),
+            asset_store: Rc::new(RefCell::new(asset_store)),
+            level: None,
+            reloaded_map: Rc::new(RefCell::new(None)),
+            loading: None,
+            editor,
         }
     }
 
@@ -60,157 +114,408 @@ impl Game<'static> {
         }
     }
 
+    /// Loads everything but textures right away and starts decoding textures in the background.
     fn load_level(&mut self, level: i32) {
-        let texture_creator: TextureCreator<WindowContext> =
-            self.context.canvas.borrow().texture_creator();
-        let mut asset_store = AssetStore::new(texture_creator);
-        asset_store.add_texture(
-            "tank".to_owned(),
-            "./assets/images/tank-tiger-right.png".to_owned(),
-        );
-        asset_store.add_texture(
-            "truck".to_owned(),
-            "./assets/images/truck-ford-left.png".to_owned(),
-        );
-        asset_store.add_texture(
-            "chopper".to_owned(),
-            "./assets/images/chopper-spritesheet.png".to_owned(),
-        );
-        asset_store.add_texture("radar".to_owned(), "./assets/images/radar.png".to_owned());
+        let started = load_manifest(&self.source, &AssetManifest::level_path(level)).and_then(|manifest| {
+            let pending = self
+                .asset_store
+                .borrow_mut()
+                .load_manifest_deferred(&manifest, level)?;
+            Ok((manifest, pending))
+        });
 
-        asset_store.add_texture(
-            "jungle".to_owned(),
-            "./assets/tilemaps/jungle.png".to_owned(),
+        let (manifest, pending) = match started {
+            Ok(started) => started,
+            Err(err) => {
+                self.logger
+                    .error(&format!("Failed to load assets for level {level}: {err}"));
+                self.is_running = false;
+                return;
+            }
+        };
+
+        // The previous level's entities are removed with the next world update, before this one spawns
+        if let Some(previous) = self.level {
+            self.world.emit_event(LevelUnloaded { level: previous });
+            self.reloaded_map.borrow_mut().take();
+            if self.world.has_system::<TilemapEditorSystem>() {
+                self.world.remove_system::<TilemapEditorSystem>();
+            }
+        }
+
+        self.logger.info(&format!(
+            "Loading {} textures for level {level}",
+            pending.len()
+        ));
+
+        let loader = TextureLoader::spawn(self.source.clone(), pending);
+        self.world.add_resource(loader.progress());
+        self.world.add_system::<LoadingScreenSystem>(
+            SystemBuilder::<LoadingScreenSystem>::new(self.world.get_component_signatures())
+                .with_system_data(LoadingScreenSystem::new(self.context.canvas.clone()))
+                .with_action(LoadingScreenSystem::action)
+                .build(),
+            false,
         );
 
-        self.world.add_resource(asset_store);
-        self.world.add_resource(Logger::new());
-        self.world.add_resource(Camera {
-            rect: Rect::new(0, 0, WINDOW_WIDTH, WINDOW_HEIGHT),
+        self.loading = Some(LevelLoading {
+            level,
+            manifest,
+            loader,
+            decoded: Vec::new(),
         });
+    }
 
-        let map = load_map("./assets/tilemaps/jungle.map");
+    /// Collects textures decoded by the worker and builds the level once all of them arrived.
+    fn update_loading(&mut self) {
+        let Some(loading) = self.loading.as_mut() else {
+            return;
+        };
+
+        for decoded in loading.loader.poll() {
+            match decoded {
+                Ok(texture) => loading.decoded.push(texture),
+                Err(err) => {
+                    self.logger.error(&format!("Failed to load texture: {err}"));
+                    self.is_running = false;
+                }
+            }
+        }
 
-        self.world.add_resource(MapDimensions {
-            width: 25 * map.tile_size as i32 * map.tile_scale as i32,
-            height: 20 * map.tile_size as i32 * map.tile_scale as i32,
-        });
+        self.world.add_resource(loading.loader.progress());
+        if !loading.loader.is_finished() {
+            return;
+        }
 
-        map.tiles.iter().enumerate().for_each(|(i, tile)| {
-            let tile_column = *tile % map.tiles_per_file_row;
-            let tile_row = *tile / map.tiles_per_file_row;
-            let map_column = i as u32 % map.tiles_per_row;
-            let map_row = i as u32 / map.tiles_per_row;
-            let mut sprite = SpriteComponent::tile(map.tile_size, map.tile_size, "jungle");
-            sprite.src = Rect::new(32 * tile_column as i32, 32 * tile_row as i32, 32, 32);
-            self.world
-                .create_entity()
-                .with_component(sprite)
-                .with_component(TransformComponent {
-                    position: Vec2::new(
-                        map_column as f32 * (map.tile_size as f32 * map.tile_scale),
-                        map_row as f32 * (map.tile_size as f32 * map.tile_scale),
-                    ),
-                    scale: Vec2::new(map.tile_scale, map.tile_scale),
-                    rotation: 0.0,
-                })
-                .finish_entity();
+        let LevelLoading {
+            level,
+            manifest,
+            decoded,
+            ..
+        } = self.loading.take().unwrap();
+        self.world.remove_system::<LoadingScreenSystem>();
+
+        let mut asset_store = self.asset_store.borrow_mut();
+        let map = asset_store
+            .add_decoded_textures(decoded)
+            .and_then(|_| asset_store.get_tilemap(&manifest.map));
+
+        // The next level's textures are in, so whatever only the previous level used can go
+        if let Some(previous) = self.level.replace(level).filter(|&previous| previous != level) {
+            let released = asset_store.unload_group(previous);
+            self.logger.info(&format!(
+                "Unloaded {} assets only level {previous} used",
+                released.len()
+            ));
+        }
+        drop(asset_store);
+
+        match map {
+            Ok(map) => self.spawn_level(level, &manifest, &map),
+            Err(err) => {
+                self.logger
+                    .error(&format!("Failed to load assets for level {level}: {err}"));
+                self.is_running = false;
+            }
+        }
+    }
+
+    fn spawn_level(&mut self, level: i32, manifest: &AssetManifest, map: &SharedMap) {
+        let asset_store = self.asset_store.clone();
+        let asset_store = asset_store.borrow();
+
+        if let Some(soundtrack) = &manifest.soundtrack {
+            let played = asset_store
+                .get_music(soundtrack)
+                .map_err(|err| err.to_string())
+                .and_then(|music| play_music(&music, -1));
+            if let Err(err) = played {
+                self.logger.warn(&format!("Failed to play soundtrack: {err}"));
+            }
+        }
+
+        self.world.add_resource(Camera {
+            rect: Rect::new(0, 0, WINDOW_WIDTH, WINDOW_HEIGHT),
         });
 
-        self.world
-            .create_entity()
-            .with_component(
-                TransformComponentBuilder::default()
-                    .position(Vec2::new(10.0, 30.0))
-                    .build()
-                    .unwrap(),
-            )
-            .with_component(RigidBodyComponent {
-                velocity: Vec2::new(50.0, 0.0),
-            })
-            .with_component(SpriteComponent::enemy(32, 32, "tank"))
-            .with_component(
-                BoxColliderComponentBuilder::default()
-                    .width(32)
-                    .height(32)
-                    .build()
-                    .unwrap(),
-            )
-            .finish_entity();
+        let tileset = asset_store.texture_handle_or_missing(&map.borrow().tileset);
+        self.add_tilemap(map.clone(), tileset);
+        if let Some(editor) = &self.editor {
+            editor
+                .borrow_mut()
+                .open(manifest.tilemaps.get(&manifest.map).cloned());
+        }
 
-        self.world
-            .create_entity()
-            .with_component(
-                TransformComponentBuilder::default()
-                    .position(Vec2::new(400.0, 30.))
-                    .build()
-                    .unwrap(),
-            )
-            .with_component(RigidBodyComponent {
-                velocity: Vec2::new(-50.0, 0.0),
-            })
-            .with_component(SpriteComponent::enemy(32, 32, "truck"))
-            .with_component(
-                BoxColliderComponentBuilder::default()
-                    .width(32)
-                    .height(32)
-                    .build()
-                    .unwrap(),
-            )
-            .finish_entity();
+        let map = map.borrow();
+        for spawn in &map.spawns {
+            if let Err(err) = self.spawn_object(spawn, &map, &asset_store) {
+                self.logger.warn(&format!("Failed to spawn '{}': {err}", spawn.name));
+            }
+        }
 
         self.world
             .create_entity()
             .with_component(
                 TransformComponentBuilder::default()
-                    .position(Vec2::new(10.0, 30.0))
-                    .rotation(90.0)
-                    .build()
-                    .unwrap(),
-            )
-            .with_component(RigidBodyComponent {
-                velocity: Vec2::new(0.0, 0.0),
-            })
-            .with_component(SpriteComponent::enemy(32, 32, "chopper"))
-            .with_component(
-                AnimationComponentBuilder::default()
-                    .num_of_frames(2)
-                    .frame_rate_speed(15)
-                    .start_time(self.context.instant.borrow().elapsed())
+                    .position(Vec2::new(WINDOW_WIDTH as f32 / 2.0 - 40.0, 10.0))
                     .build()
                     .unwrap(),
             )
-            .with_component(
-                KeyboardControlledComponentBuilder::default()
-                    .up_velocity(Vec2::new(0.0, -100.0))
-                    .left_velocity(Vec2::new(-100.0, 0.0))
-                    .down_velocity(Vec2::new(0.0, 100.0))
-                    .right_velocity(Vec2::new(100.0, 0.0))
-                    .build()
-                    .unwrap(),
-            )
-            .with_component(CameraFollowComponent)
+            .with_component(TextLabelComponent::ui(
+                &format!("LEVEL {level}"),
+                "charriot",
+                Color::GREEN,
+            ))
             .finish_entity();
 
-        self.world
-            .create_entity()
-            .with_component(
-                TransformComponentBuilder::default()
-                    .position(Vec2::new(730.0, 20.0))
-                    .build()
-                    .unwrap(),
-            )
-            .with_component(SpriteComponent::ui(64, 64, "radar"))
-            .with_component(
-                AnimationComponentBuilder::default()
-                    .num_of_frames(8)
-                    .frame_rate_speed(12)
-                    .start_time(self.context.instant.borrow().elapsed())
-                    .build()
-                    .unwrap(),
-            )
-            .finish_entity();
+        // The watcher follows the map of the current level
+        if self.dev_mode {
+            self.logger.info("Dev mode enabled, watching assets for changes");
+            if self.world.has_system::<HotReloadSystem>() {
+                self.world.remove_system::<HotReloadSystem>();
+            }
+            self.world.add_system::<HotReloadSystem>(
+                SystemBuilder::<HotReloadSystem>::new(self.world.get_component_signatures())
+                    .with_system_data(HotReloadSystem::new(
+                        manifest.map.clone(),
+                        self.reloaded_map.clone(),
+                    ))
+                    .with_action(HotReloadSystem::action)
+                    .with_component::<TileComponent>()
+                    .build(),
+                false,
+            );
+        }
+    }
+
+    /// Makes `map` the drawn level and spawns the colliders merged from its solid, water and slow tiles.
+    fn add_tilemap(&mut self, shared_map: SharedMap, tileset: TextureHandle) {
+        let map = shared_map.borrow();
+        for collider in map.colliders() {
+            self.world
+                .create_entity()
+                .with_component(TileComponent)
+                .with_component(StaticColliderComponent {
+                    surface: collider.surface,
+                })
+                .with_component(
+                    TransformComponentBuilder::default()
+                        .position(Vec2::new(
+                            collider.column as f32 * map.scaled_tile_size(),
+                            collider.row as f32 * map.scaled_tile_size(),
+                        ))
+                        .build()
+                        .unwrap(),
+                )
+                .with_component(
+                    BoxColliderComponentBuilder::default()
+                        .width((collider.width as f32 * map.scaled_tile_size()) as u32)
+                        .height((collider.height as f32 * map.scaled_tile_size()) as u32)
+                        .build()
+                        .unwrap(),
+                )
+                .finish_entity();
+        }
+
+        self.world.add_resource(MapDimensions::from_map(&map));
+        self.world.add_resource(NavGrid::from_map(&map));
+        drop(map);
+        self.world.add_resource(Tilemap {
+            map: shared_map,
+            tileset,
+        });
+    }
 
+    /// Instantiates a spawn point of the map by its kind.
+    ///
+    /// Map objects are placed in unscaled map pixels, HUD elements like the radar in screen pixels.
+    fn spawn_object(
+        &mut self,
+        spawn: &SpawnPoint,
+        map: &Map,
+        asset_store: &AssetStore,
+    ) -> Result<(), String> {
+        let position = Vec2::new(spawn.x, spawn.y) * map.tile_scale;
+        let sprite = asset_store
+            .texture_handle_or_missing(&spawn.property("sprite", spawn.name.clone())?);
+        let start_time = self.context.instant.borrow().elapsed();
+
+        match spawn.kind.as_str() {
+            "enemy" => {
+                let mut entity = self
+                    .world
+                    .create_entity()
+                    .with_component(
+                        TransformComponentBuilder::default()
+                            .position(position)
+                            .build()
+                            .unwrap(),
+                    )
+                    .with_component(RigidBodyComponent {
+                        velocity: Vec2::new(
+                            spawn.property("velocity_x", 0.0)?,
+                            spawn.property("velocity_y", 0.0)?,
+                        ),
+                    })
+                    .with_component(SpriteComponent::enemy(32, 32, sprite))
+                    .with_component(
+                        BoxColliderComponentBuilder::default()
+                            .width(32)
+                            .height(32)
+                            .build()
+                            .unwrap(),
+                    )
+                    .with_component(HealthComponent::new(
+                        spawn.property("health", 50)?,
+                        Faction::Enemy,
+                    ))
+                    .with_component(
+                        DamageComponentBuilder::default()
+                            .amount(spawn.property("damage", 25)?)
+                            .faction(Faction::Enemy)
+                            .build()
+                            .unwrap(),
+                    );
+
+                // Enemies with a goal drive there around water and trees instead of straight on
+                if spawn.properties.contains_key("goal_x")
+                    && spawn.properties.contains_key("goal_y")
+                {
+                    entity = entity.with_component(
+                        PathFollowComponentBuilder::default()
+                            .goal(
+                                Vec2::new(
+                                    spawn.property("goal_x", 0.0)?,
+                                    spawn.property("goal_y", 0.0)?,
+                                ) * map.tile_scale,
+                            )
+                            .speed(spawn.property("speed", 50.0)?)
+                            .build()
+                            .unwrap(),
+                    );
+                }
+                // Turrets keep firing along `fire_x`, `fire_y`, to the right by default
+                if spawn.properties.contains_key("fire_rate") {
+                    entity = entity.with_component(
+                        ProjectileEmitterComponentBuilder::default()
+                            .direction(Vec2::new(
+                                spawn.property("fire_x", 1.0)?,
+                                spawn.property("fire_y", 0.0)?,
+                            ))
+                            .sprite(asset_store.texture_handle_or_missing("bullet"))
+                            .faction(Faction::Enemy)
+                            .fire_rate(spawn.property("fire_rate", 1.0)?)
+                            .speed(spawn.property("bullet_speed", 300.0)?)
+                            .damage(spawn.property("bullet_damage", 10)?)
+                            .build()
+                            .unwrap(),
+                    );
+                }
+                entity.finish_entity();
+            }
+            "player" => {
+                let speed = spawn.property("speed", 100.0)?;
+                self.world
+                    .create_entity()
+                    .with_component(
+                        TransformComponentBuilder::default()
+                            .position(position)
+                            .rotation(spawn.property("rotation", 0.0)?)
+                            .build()
+                            .unwrap(),
+                    )
+                    .with_component(RigidBodyComponent {
+                        velocity: Vec2::new(0.0, 0.0),
+                    })
+                    .with_component(SpriteComponent::enemy(32, 32, sprite))
+                    .with_component(
+                        AnimationComponentBuilder::default()
+                            .clip(spawn.property("clip", "fly_right".to_owned())?)
+                            .start_time(start_time)
+                            .build()
+                            .unwrap(),
+                    )
+                    .with_component(
+                        KeyboardControlledComponentBuilder::default()
+                            .up_velocity(Vec2::new(0.0, -speed))
+                            .left_velocity(Vec2::new(-speed, 0.0))
+                            .down_velocity(Vec2::new(0.0, speed))
+                            .right_velocity(Vec2::new(speed, 0.0))
+                            .build()
+                            .unwrap(),
+                    )
+                    .with_component(CameraFollowComponent)
+                    .with_component(AirborneComponent)
+                    .with_component(
+                        BoxColliderComponentBuilder::default()
+                            .width(32)
+                            .height(32)
+                            .build()
+                            .unwrap(),
+                    )
+                    .with_component(
+                        ProjectileEmitterComponentBuilder::default()
+                            .direction(Vec2::new(1.0, 0.0))
+                            .sprite(asset_store.texture_handle_or_missing("bullet"))
+                            .faction(Faction::Player)
+                            .fire_rate(spawn.property("fire_rate", 4.0)?)
+                            .speed(spawn.property("bullet_speed", 400.0)?)
+                            .damage(spawn.property("bullet_damage", 10)?)
+                            .automatic(false)
+                            .build()
+                            .unwrap(),
+                    )
+                    .with_component(HealthComponent::new(
+                        spawn.property("health", 100)?,
+                        Faction::Player,
+                    ))
+                    .with_component(
+                        AudioSourceComponentBuilder::default()
+                            .sound_id(spawn.property("sound", "helicopter".to_owned())?)
+                            .build()
+                            .unwrap(),
+                    )
+                    .finish_entity();
+            }
+            "radar" => {
+                self.world
+                    .create_entity()
+                    .with_component(
+                        TransformComponentBuilder::default()
+                            .position(Vec2::new(spawn.x, spawn.y))
+                            .build()
+                            .unwrap(),
+                    )
+                    .with_component(SpriteComponent::ui(64, 64, sprite))
+                    .with_component(
+                        AnimationComponentBuilder::default()
+                            .clip(spawn.property("clip", "sweep".to_owned())?)
+                            .start_time(start_time)
+                            .build()
+                            .unwrap(),
+                    )
+                    .finish_entity();
+            }
+            kind => return Err(format!("unknown kind '{kind}'")),
+        }
+        Ok(())
+    }
+
+    fn setup(&mut self) {
+        self.world.add_resource(Logger::new());
+        self.world.add_resource(self.asset_store.clone());
+        self.add_systems();
+
+        self.world.events().subscribe(collision_event_handler);
+        self.world.events().subscribe(entity_died_handler);
+        self.world.events().subscribe(key_pressed_hanlder);
+        self.world.events().subscribe(level_unloaded_handler);
+
+        self.load_level(1);
+    }
+
+    /// Registers the systems every level runs, they wait for the first level to finish loading.
+    fn add_systems(&mut self) {
         self.world.add_system::<MovementSystem>(
             SystemBuilder::<MovementSystem>::new(self.world.get_component_signatures())
                 .with_system_data(MovementSystem)
@@ -221,9 +526,23 @@ impl Game<'static> {
             false,
         );
 
+        self.world.add_system::<PathFollowSystem>(
+            SystemBuilder::<PathFollowSystem>::new(self.world.get_component_signatures())
+                .with_system_data(PathFollowSystem)
+                .with_action(PathFollowSystem::action)
+                .with_component::<TransformComponent>()
+                .with_component::<RigidBodyComponent>()
+                .with_component::<PathFollowComponent>()
+                .build(),
+            false,
+        );
+
         self.world.add_system::<RenderSystem>(
             SystemBuilder::<RenderSystem>::new(self.world.get_component_signatures())
-                .with_system_data(RenderSystem::new(self.context.canvas.clone()))
+                .with_system_data(RenderSystem::new(
+                    self.context.canvas.clone(),
+                    self.context.instant.clone(),
+                ))
                 .with_action(RenderSystem::action)
                 .with_component::<TransformComponent>()
                 .with_component::<SpriteComponent>()
@@ -251,6 +570,34 @@ impl Game<'static> {
             false,
         );
 
+        self.world.add_system::<HealthSystem>(
+            SystemBuilder::<HealthSystem>::new(self.world.get_component_signatures())
+                .with_system_data(HealthSystem)
+                .with_action(HealthSystem::action)
+                .with_component::<HealthComponent>()
+                .build(),
+            false,
+        );
+
+        self.world.add_system::<ProjectileEmitterSystem>(
+            SystemBuilder::<ProjectileEmitterSystem>::new(self.world.get_component_signatures())
+                .with_system_data(ProjectileEmitterSystem)
+                .with_action(ProjectileEmitterSystem::action)
+                .with_component::<TransformComponent>()
+                .with_component::<ProjectileEmitterComponent>()
+                .build(),
+            false,
+        );
+
+        self.world.add_system::<ProjectileSystem>(
+            SystemBuilder::<ProjectileSystem>::new(self.world.get_component_signatures())
+                .with_system_data(ProjectileSystem)
+                .with_action(ProjectileSystem::action)
+                .with_component::<ProjectileComponent>()
+                .build(),
+            false,
+        );
+
         self.world.add_system::<CameraMovementSystem>(
             SystemBuilder::<CameraMovementSystem>::new(self.world.get_component_signatures())
                 .with_system_data(CameraMovementSystem)
@@ -261,16 +608,24 @@ impl Game<'static> {
             false,
         );
 
-        self.world.events().subscribe(collision_event_handler);
-        self.world.events().subscribe(key_pressed_hanlder);
-    }
-
-    fn setup(&mut self) {
-        self.load_level(1);
+        self.world.add_system::<AudioSystem>(
+            SystemBuilder::<AudioSystem>::new(self.world.get_component_signatures())
+                .with_system_data(AudioSystem::new())
+                .with_action(AudioSystem::action)
+                .with_component::<AudioSourceComponent>()
+                .build(),
+            false,
+        );
     }
 
     fn process_input(&mut self, event_pump: &mut EventPump) {
         for event in event_pump.poll_iter() {
+            if let Some(editor) = &self.editor {
+                if self.world.has_system::<TilemapEditorSystem>() {
+                    editor.borrow_mut().handle_event(&event);
+                }
+            }
+
             match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
@@ -299,6 +654,32 @@ impl Game<'static> {
                         );
                     }
                 }
+                Event::KeyDown {
+                    keycode: Some(Keycode::E),
+                    ..
+                } if self.editor.is_some() && self.loading.is_none() => {
+                    if self.world.has_system::<TilemapEditorSystem>() {
+                        self.logger.info("Closing the tilemap editor");
+                        self.world.remove_system::<TilemapEditorSystem>();
+                    } else {
+                        self.logger.info("Opening the tilemap editor");
+                        let editor = self.editor.clone().unwrap();
+                        self.world.add_system::<TilemapEditorSystem>(
+                            SystemBuilder::<TilemapEditorSystem>::new(
+                                self.world.get_component_signatures(),
+                            )
+                            .with_system_data(TilemapEditorSystem::new(
+                                editor,
+                                self.context.canvas.clone(),
+                                self.reloaded_map.clone(),
+                            ))
+                            .with_action(TilemapEditorSystem::action)
+                            .with_component::<TileComponent>()
+                            .build(),
+                            false,
+                        );
+                    }
+                }
                 Event::KeyDown {
                     keycode: Some(code),
                     ..
@@ -316,9 +697,25 @@ impl Game<'static> {
         }
 
         self.world.update();
+        if self.loading.is_some() {
+            self.update_loading();
+            return;
+        }
+
+        self.world.update_system::<PathFollowSystem>();
         self.world.update_system::<MovementSystem>();
         self.world.update_system::<CollisionSystem>();
+        self.world.update_system::<HealthSystem>();
+        self.world.update_system::<ProjectileEmitterSystem>();
+        self.world.update_system::<ProjectileSystem>();
         self.world.update_system::<CameraMovementSystem>();
+        self.world.update_system::<AudioSystem>();
+        self.world.update_system::<HotReloadSystem>();
+
+        let reloaded_map = self.reloaded_map.borrow_mut().take();
+        if let Some((map, tileset)) = reloaded_map {
+            self.add_tilemap(map, tileset);
+        }
     }
 
     pub fn render(&mut self, _: &Duration) {
@@ -328,9 +725,13 @@ impl Game<'static> {
             .set_draw_color(Color::RGB(21, 21, 21));
         self.context.canvas.borrow_mut().clear();
 
-        self.world.update_system::<AnimationSystem>();
-        self.world.update_system::<RenderSystem>();
-        self.world.update_system::<DebugSystem>();
+        self.world.update_system::<LoadingScreenSystem>();
+        if self.loading.is_none() {
+            self.world.update_system::<AnimationSystem>();
+            self.world.update_system::<RenderSystem>();
+            self.world.update_system::<DebugSystem>();
+            self.world.update_system::<TilemapEditorSystem>();
+        }
 
         self.context.canvas.borrow_mut().present()
     }
@@ -345,3 +746,54 @@ pub struct MapDimensions {
     pub height: i32,
     pub width: i32,
 }
+
+impl MapDimensions {
+    pub fn from_map(map: &Map) -> Self {
+        let (width, height) = map.size();
+        Self {
+            width: width as i32,
+            height: height as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::Duration;
+
+    use super::Game;
+
+    /// Runs frames until the level being loaded is spawned.
+    fn finish_loading(game: &mut Game<'static>) {
+        while game.is_running && game.loading.is_some() {
+            game.update(&Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn switches_levels_keeping_shared_assets() {
+        let _sdl_lock = crate::sdl::SDL_TEST_LOCK
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        std::env::set_var("SDL_VIDEODRIVER", "dummy");
+        std::env::set_var("SDL_AUDIODRIVER", "dummy");
+        let mut game = Game::new(false);
+
+        game.setup();
+        finish_loading(&mut game);
+        assert_eq!(game.level, Some(1));
+        let jungle = game.asset_store.borrow().texture_handle("jungle").unwrap();
+
+        game.load_level(2);
+        finish_loading(&mut game);
+        game.update(&Duration::ZERO);
+        game.render(&Duration::ZERO);
+
+        assert!(game.is_running);
+        assert_eq!(game.level, Some(2));
+        let asset_store = game.asset_store.borrow();
+        assert_eq!(asset_store.texture_handle("jungle").unwrap(), jungle);
+        assert!(asset_store.get_tilemap(&"arena".to_owned()).is_ok());
+        assert!(asset_store.get_tilemap(&"jungle".to_owned()).is_err());
+    }
+}
@@ -0,0 +1,400 @@
+//! Seeded generator of jungle arenas, the same seed and settings always give the same `Map`.
+//!
+//! Terrain is decided per cell corner: fractal value noise floods the low corners into lakes,
+//! rivers are carved from the top to the bottom edge and a straight road of land crosses the
+//! map from left to right, bridging any water in its way. Every cell then takes the shore tile
+//! matching its four corners, the land cells grass, flowers, trees and rocks.
+
+use std::collections::HashMap;
+
+use crate::map::{LayerKind, Map, MapBuilder, SpawnPoint, TileAnimation, TileLayer, TileSurface};
+
+const TILESET: &str = "jungle";
+const TILE_SIZE: u32 = 32;
+const TILESET_COLUMNS: u32 = 10;
+const TILESET_ROWS: u32 = 4;
+
+const GRASS: [u32; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+const FLOWERS: [u32; 2] = [23, 24];
+const ROCKS: [u32; 2] = [25, 26];
+const TREES: [u32; 3] = [27, 28, 29];
+const WATER: u32 = 21;
+
+/// Jungle tiles by the corners of the cell that are water, bit 0 is the north west corner,
+/// then north east, south west and south east. Cells with only two diagonal corners under
+/// water have no tile, the generator never leaves them.
+const SHORE_TILES: [Option<u32>; 16] = [
+    None,
+    Some(12),
+    Some(14),
+    Some(13),
+    Some(10),
+    Some(11),
+    None,
+    Some(17),
+    Some(15),
+    None,
+    Some(22),
+    Some(18),
+    Some(9),
+    Some(16),
+    Some(19),
+    Some(WATER),
+];
+
+/// Corners per noise lattice cell, larger values give larger lakes.
+const NOISE_SCALE: f32 = 8.0;
+
+/// Settings of a generated arena, build it with `MapGeneratorBuilder` and call `generate`.
+#[derive(Debug, Clone, Builder)]
+#[builder(build_fn(validate = "Self::validate"))]
+pub struct MapGenerator {
+    pub seed: u64,
+    #[builder(default = "40")]
+    pub columns: u32,
+    #[builder(default = "30")]
+    pub rows: u32,
+    #[builder(default = "2.0")]
+    pub tile_scale: f32,
+    /// Noise below this level floods, about 0.35 gives a few lakes and 0.5 half the map.
+    #[builder(default = "0.35")]
+    pub water_level: f32,
+    #[builder(default = "1")]
+    pub rivers: u32,
+    /// A road guarantees a way across the map for vehicles, the level's vehicles spawn on it.
+    #[builder(default = "true")]
+    pub road: bool,
+}
+
+impl MapGeneratorBuilder {
+    fn validate(&self) -> Result<(), String> {
+        let (columns, rows) = (self.columns.unwrap_or(40), self.rows.unwrap_or(30));
+        if columns < 8 || rows < 6 {
+            return Err(format!(
+                "arenas need at least 8x6 tiles, got {columns}x{rows}"
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl MapGenerator {
+    pub fn generate(&self) -> Map {
+        let mut rng = Rng(self.seed);
+        let corners = Corners::new(self.columns + 1, self.rows + 1);
+        let mut water: Vec<bool> = corners
+            .iter()
+            .map(|(x, y)| {
+                fractal_noise(self.seed, x as f32 / NOISE_SCALE, y as f32 / NOISE_SCALE)
+                    < self.water_level
+            })
+            .collect();
+
+        for _ in 0..self.rivers {
+            carve_river(&corners, &mut water, &mut rng);
+        }
+
+        let road_row = self.road.then(|| 2 + rng.below(self.rows - 5));
+        if let Some(road_row) = road_row {
+            for y in road_row..=road_row + 2 {
+                for x in 0..corners.width {
+                    water[corners.index(x, y)] = false;
+                }
+            }
+        }
+
+        remove_diagonal_water(&corners, &mut water);
+
+        let on_road = |row: u32| road_row.is_some_and(|road| row == road || row == road + 1);
+        let tiles = (0..self.rows)
+            .flat_map(|row| (0..self.columns).map(move |column| (column, row)))
+            .map(|(column, row)| {
+                let mask = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (dx, dy))| water[corners.index(column + dx, row + dy)])
+                    .fold(0, |mask, (bit, _)| mask | 1 << bit);
+                match SHORE_TILES[mask] {
+                    Some(tile) => tile,
+                    None if on_road(row) => pick(&GRASS, hash(self.seed, column, row)),
+                    None => self.land_tile(column, row),
+                }
+            })
+            .collect();
+
+        let mut spawns = vec![SpawnPoint {
+            name: "radar".to_owned(),
+            kind: "radar".to_owned(),
+            x: 730.0,
+            y: 20.0,
+            properties: HashMap::new(),
+        }];
+        let row = road_row.unwrap_or(self.rows / 2);
+        let y = (row * TILE_SIZE) as f32;
+        spawns.push(self.spawn("chopper", "player", 1, y, &[("rotation", "90")]));
+        if road_row.is_some() {
+            let last = self.columns - 3;
+            spawns.push(self.spawn("tank", "enemy", 2, y, &[("velocity_x", "50")]));
+            spawns.push(self.spawn("truck", "enemy", last, y, &[("velocity_x", "-50")]));
+        }
+
+        MapBuilder::default()
+            .tileset(TILESET)
+            .tile_size(TILE_SIZE)
+            .tile_scale(self.tile_scale)
+            .tiles_per_row(self.columns)
+            .tiles_per_file_row(TILESET_COLUMNS)
+            .tileset_rows(TILESET_ROWS)
+            .tile_surfaces(jungle_surfaces())
+            .tile_animations(HashMap::from([(
+                WATER,
+                TileAnimation {
+                    frames: vec![WATER, 30, 31, 32],
+                    durations: vec![300; 4],
+                },
+            )]))
+            .layers(vec![TileLayer {
+                kind: LayerKind::Ground,
+                tiles,
+            }])
+            .spawns(spawns)
+            .build()
+            .unwrap()
+    }
+
+    /// Trees grow in clusters where a second noise is high, flowers and rocks are scattered.
+    fn land_tile(&self, column: u32, row: u32) -> u32 {
+        let roll = hash(self.seed, column, row);
+        let forest = fractal_noise(
+            self.seed.wrapping_add(1),
+            column as f32 / NOISE_SCALE,
+            row as f32 / NOISE_SCALE,
+        );
+        let chance = (roll >> 32) as f32 / u32::MAX as f32;
+
+        if forest > 0.55 && chance < 0.5 {
+            pick(&TREES, roll)
+        } else if chance < 0.04 {
+            pick(&ROCKS, roll)
+        } else if chance < 0.1 {
+            pick(&FLOWERS, roll)
+        } else {
+            pick(&GRASS, roll)
+        }
+    }
+
+    fn spawn(
+        &self,
+        name: &str,
+        kind: &str,
+        column: u32,
+        y: f32,
+        properties: &[(&str, &str)],
+    ) -> SpawnPoint {
+        SpawnPoint {
+            name: name.to_owned(),
+            kind: kind.to_owned(),
+            x: (column * TILE_SIZE) as f32,
+            y,
+            properties: properties
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// Surfaces of the jungle tileset, the shores that are mostly water block like open water.
+fn jungle_surfaces() -> HashMap<u32, TileSurface> {
+    let mut surfaces = HashMap::new();
+    for tile in [16, 17, 18, 19, WATER] {
+        surfaces.insert(tile, TileSurface::Water);
+    }
+    for tile in [27, 28] {
+        surfaces.insert(tile, TileSurface::Solid);
+    }
+    for tile in ROCKS {
+        surfaces.insert(tile, TileSurface::Slow);
+    }
+    surfaces
+}
+
+/// Grid of cell corners, one more than the cells in each direction.
+struct Corners {
+    width: u32,
+    height: u32,
+}
+
+impl Corners {
+    fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| (x, y)))
+    }
+}
+
+/// Meanders two corners wide from the top edge to the bottom one.
+fn carve_river(corners: &Corners, water: &mut [bool], rng: &mut Rng) {
+    let mut x = 1 + rng.below(corners.width - 3);
+    for y in 0..corners.height {
+        water[corners.index(x, y)] = true;
+        water[corners.index(x + 1, y)] = true;
+        match rng.below(4) {
+            0 if x > 0 => x -= 1,
+            1 if x + 2 < corners.width => x += 1,
+            _ => {}
+        }
+        // Keeps the banks of a bend connected so the river stays two corners wide
+        water[corners.index(x, y)] = true;
+        water[corners.index(x + 1, y)] = true;
+    }
+}
+
+/// Turns water corners to land where a cell would have water only in two opposite corners.
+fn remove_diagonal_water(corners: &Corners, water: &mut [bool]) {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for y in 0..corners.height - 1 {
+            for x in 0..corners.width - 1 {
+                let [nw, ne, sw, se] = [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)]
+                    .map(|(x, y)| corners.index(x, y));
+                let diagonal = match (water[nw], water[ne], water[sw], water[se]) {
+                    (true, false, false, true) => [nw, se],
+                    (false, true, true, false) => [ne, sw],
+                    _ => continue,
+                };
+                for corner in diagonal {
+                    water[corner] = false;
+                }
+                changed = true;
+            }
+        }
+    }
+}
+
+fn pick(tiles: &[u32], roll: u64) -> u32 {
+    tiles[(roll % tiles.len() as u64) as usize]
+}
+
+/// Deterministic random numbers, a SplitMix64 sequence.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix(self.0)
+    }
+
+    fn below(&mut self, bound: u32) -> u32 {
+        (self.next() % bound.max(1) as u64) as u32
+    }
+}
+
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+fn hash(seed: u64, x: u32, y: u32) -> u64 {
+    mix(seed ^ mix((x as u64) << 32 | y as u64))
+}
+
+/// Value noise in `0..1`, smoothly interpolated between random values at integer points.
+fn value_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (sx, sy) = (fx * fx * (3.0 - 2.0 * fx), fy * fy * (3.0 - 2.0 * fy));
+    let value = |dx: u32, dy: u32| {
+        let roll = hash(seed, x0 as u32 + dx, y0 as u32 + dy);
+        (roll >> 40) as f32 / (1u64 << 24) as f32
+    };
+
+    let top = value(0, 0) + (value(1, 0) - value(0, 0)) * sx;
+    let bottom = value(0, 1) + (value(1, 1) - value(0, 1)) * sx;
+    top + (bottom - top) * sy
+}
+
+/// Three octaves of value noise, each twice as fine and half as strong as the one before.
+fn fractal_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let mut total = 0.0;
+    let mut weight = 1.0;
+    let mut frequency = 1.0;
+    for octave in 0..3 {
+        total += value_noise(seed.wrapping_add(octave), x * frequency, y * frequency) * weight;
+        weight *= 0.5;
+        frequency *= 2.0;
+    }
+    total / 1.75
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MapGeneratorBuilder, SHORE_TILES};
+    use crate::map::{parse_map, serialize_map, EMPTY_TILE};
+
+    #[test]
+    fn generates_the_same_map_for_a_seed() {
+        let generator = MapGeneratorBuilder::default().seed(7).build().unwrap();
+        let map = generator.generate();
+
+        assert_eq!(map, generator.generate());
+        assert_eq!(map.size(), (40 * 64, 30 * 64));
+        assert_ne!(
+            map,
+            MapGeneratorBuilder::default()
+                .seed(8)
+                .build()
+                .unwrap()
+                .generate()
+        );
+        assert_eq!(parse_map(&serialize_map(&map).unwrap()).unwrap(), map);
+    }
+
+    #[test]
+    fn keeps_tiles_valid_and_the_road_open() {
+        for seed in 0..20 {
+            let map = MapGeneratorBuilder::default()
+                .seed(seed)
+                .columns(24)
+                .rows(16)
+                .water_level(0.45)
+                .rivers(2)
+                .build()
+                .unwrap()
+                .generate();
+            let tiles = &map.layers[0].tiles;
+
+            assert!(tiles
+                .iter()
+                .all(|tile| *tile != EMPTY_TILE && *tile < map.tile_count()));
+
+            let truck = map
+                .spawns
+                .iter()
+                .find(|spawn| spawn.name == "truck")
+                .unwrap();
+            let road_row = truck.y as u32 / map.tile_size;
+            for row in road_row..road_row + 2 {
+                for column in 0..map.tiles_per_row {
+                    let cell = (row * map.tiles_per_row + column) as usize;
+                    assert_eq!(map.surface_at(cell), None, "seed {seed} cell {cell}");
+                }
+            }
+        }
+
+        assert!(SHORE_TILES[0b0110].is_none() && SHORE_TILES[0b1001].is_none());
+        assert!(MapGeneratorBuilder::default()
+            .seed(1)
+            .columns(4)
+            .build()
+            .is_err());
+    }
+}
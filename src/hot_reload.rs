@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+pub const WATCHED_DIRECTORIES: [&str; 2] = ["./assets/images", "./assets/tilemaps"];
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Polls directories for files whose modification time changed since the last poll.
+pub struct AssetWatcher {
+    directories: Vec<PathBuf>,
+    modified: HashMap<PathBuf, SystemTime>,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+impl AssetWatcher {
+    pub fn new(directories: &[&str], interval: Duration) -> Self {
+        let mut watcher = Self {
+            directories: directories.iter().map(PathBuf::from).collect(),
+            modified: HashMap::new(),
+            interval,
+            last_poll: Instant::now(),
+        };
+        watcher.modified = watcher.scan();
+        watcher
+    }
+
+    /// Returns the files that were created or modified, at most once per poll interval.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let current = self.scan();
+        let changed = current
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        self.modified = current;
+        changed
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+        self.directories
+            .iter()
+            .filter_map(|directory| fs::read_dir(directory).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let modified = entry.metadata().and_then(|meta| meta.modified()).ok()?;
+                Some((entry.path(), modified))
+            })
+            .collect()
+    }
+}
+
+/// Compares paths the way they resolve on disk so `./assets/x.png` matches `assets/x.png`.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::{self, File},
+        time::{Duration, SystemTime},
+    };
+
+    use super::AssetWatcher;
+
+    #[test]
+    fn reports_modified_files() {
+        let directory =
+            std::env::temp_dir().join(format!("game2d-hot-reload-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let file = directory.join("tile.png");
+        fs::write(&file, b"first").unwrap();
+
+        let mut watcher = AssetWatcher::new(&[directory.to_str().unwrap()], Duration::ZERO);
+        assert!(watcher.poll().is_empty());
+
+        File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+
+        assert_eq!(watcher.poll(), vec![file.clone()]);
+        assert!(watcher.poll().is_empty());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,25 @@
+mod game;
+mod sdl;
+mod logger;
+mod components;
+mod systems;
+mod asset_source;
+mod asset_store;
+mod atlas;
+mod audio;
+mod manifest;
+pub mod map;
+pub mod generator;
+mod pathfinding;
+mod sprite_sheet;
+mod tiled;
+pub mod pack;
+mod loader;
+mod hot_reload;
+mod resources;
+mod editor;
+
+pub use game::Game;
+
+#[macro_use]
+extern crate derive_builder;
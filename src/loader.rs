@@ -0,0 +1,136 @@
+use std::{
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread::{self, JoinHandle},
+};
+
+use image::RgbaImage;
+
+use crate::asset_source::AssetSource;
+use crate::asset_store::{AssetError, AssetId};
+use crate::resources::LoadingProgress;
+
+/// Pixels read and decoded off the main thread, ready to be uploaded to the renderer.
+pub struct DecodedTexture {
+    pub asset_id: AssetId,
+    pub asset_path: String,
+    pub image: RgbaImage,
+}
+
+pub fn decode_texture(
+    source: &AssetSource,
+    asset_id: AssetId,
+    asset_path: String,
+) -> Result<DecodedTexture, AssetError> {
+    let data = source.read(&asset_path)?;
+
+    match image::load_from_memory(&data) {
+        Ok(image) => Ok(DecodedTexture {
+            asset_id,
+            asset_path,
+            image: image.into_rgba8(),
+        }),
+        Err(err) => Err(AssetError::Decode {
+            path: asset_path,
+            reason: err.to_string(),
+        }),
+    }
+}
+
+/// Decodes textures on a worker thread, the main thread polls for finished images.
+pub struct TextureLoader {
+    receiver: Receiver<Result<DecodedTexture, AssetError>>,
+    total: usize,
+    received: usize,
+    disconnected: bool,
+    _worker: JoinHandle<()>,
+}
+
+impl TextureLoader {
+    pub fn spawn(source: AssetSource, textures: Vec<(AssetId, String)>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let total = textures.len();
+
+        let worker = thread::spawn(move || {
+            for (asset_id, asset_path) in textures {
+                if sender
+                    .send(decode_texture(&source, asset_id, asset_path))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            receiver,
+            total,
+            received: 0,
+            disconnected: false,
+            _worker: worker,
+        }
+    }
+
+    /// Returns every texture decoded since the last poll without blocking.
+    pub fn poll(&mut self) -> Vec<Result<DecodedTexture, AssetError>> {
+        let mut decoded = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(texture) => decoded.push(texture),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.disconnected = true;
+                    break;
+                }
+            }
+        }
+        self.received += decoded.len();
+        decoded
+    }
+
+    /// True once every texture arrived, or the worker stopped early.
+    pub fn is_finished(&self) -> bool {
+        self.received == self.total || self.disconnected
+    }
+
+    pub fn progress(&self) -> LoadingProgress {
+        LoadingProgress {
+            loaded: self.received,
+            total: self.total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{thread, time::Duration};
+
+    use super::TextureLoader;
+    use crate::asset_source::AssetSource;
+
+    #[test]
+    fn decodes_textures_in_background() {
+        let mut loader = TextureLoader::spawn(
+            AssetSource::Disk,
+            vec![
+                (
+                    "tank".to_owned(),
+                    "./assets/images/tank-tiger-right.png".to_owned(),
+                ),
+                ("radar".to_owned(), "./assets/images/radar.png".to_owned()),
+                ("ghost".to_owned(), "./assets/images/ghost.png".to_owned()),
+            ],
+        );
+
+        let mut decoded = Vec::new();
+        while !loader.is_finished() {
+            decoded.extend(loader.poll());
+            thread::sleep(Duration::from_millis(1));
+        }
+        decoded.extend(loader.poll());
+
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(loader.progress().fraction(), 1.0);
+        assert_eq!(decoded[1].as_ref().unwrap().image.dimensions(), (512, 64));
+        assert!(decoded[2].is_err());
+    }
+}
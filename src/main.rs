@@ -1,18 +1,7 @@
-mod game;
-mod sdl;
-mod logger;
-mod components;
-mod systems;
-mod asset_store;
-mod map;
-mod resources;
-
-use game::Game;
-
-#[macro_use]
-extern crate derive_builder;
+use game2d::Game;
 
 fn main() {
-    let mut game = Game::new();
+    let dev_mode = std::env::args().any(|arg| arg == "--dev");
+    let mut game = Game::new(dev_mode);
     game.run(); 
 }
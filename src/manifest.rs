@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::asset_source::AssetSource;
+use crate::asset_store::{AssetError, AssetId};
+
+/// Lists every asset a level needs, keyed by the asset id the game refers to it with.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AssetManifest {
+    /// Id of the tilemap from `tilemaps` the level is built from.
+    pub map: AssetId,
+    /// Id of the track from `music` played while the level runs.
+    #[serde(default)]
+    pub soundtrack: Option<AssetId>,
+    #[serde(default)]
+    pub textures: HashMap<AssetId, String>,
+    #[serde(default)]
+    pub fonts: HashMap<AssetId, FontEntry>,
+    #[serde(default)]
+    pub sounds: HashMap<AssetId, String>,
+    #[serde(default)]
+    pub music: HashMap<AssetId, String>,
+    #[serde(default)]
+    pub tilemaps: HashMap<AssetId, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FontEntry {
+    pub path: String,
+    pub size: u16,
+}
+
+impl AssetManifest {
+    pub fn level_path(level: i32) -> String {
+        format!("./assets/levels/level{level}.toml")
+    }
+
+    /// Every file referenced by the manifest that the source does not contain.
+    pub fn missing_files(&self, source: &AssetSource) -> Vec<String> {
+        let mut missing: Vec<String> = self
+            .textures
+            .values()
+            .chain(self.fonts.values().map(|font| &font.path))
+            .chain(self.sounds.values())
+            .chain(self.music.values())
+            .chain(self.tilemaps.values())
+            .filter(|path| !source.exists(path))
+            .cloned()
+            .collect();
+        missing.sort();
+        missing
+    }
+}
+
+pub fn load_manifest(
+    source: &AssetSource,
+    manifest_file: &str,
+) -> Result<AssetManifest, AssetError> {
+    let data = source.read_to_string(manifest_file)?;
+
+    toml::from_str(&data).map_err(|err| AssetError::Decode {
+        path: manifest_file.to_owned(),
+        reason: err.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{load_manifest, AssetManifest};
+    use crate::asset_source::AssetSource;
+
+    #[test]
+    fn level_one_manifest_is_complete() {
+        let manifest = load_manifest(&AssetSource::Disk, &AssetManifest::level_path(1)).unwrap();
+
+        assert_eq!(manifest.map, "jungle");
+        assert!(manifest.tilemaps.contains_key(&manifest.map));
+        assert!(manifest.missing_files(&AssetSource::Disk).is_empty());
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let manifest: AssetManifest = toml::from_str(
+            r#"
+            map = "void"
+
+            [textures]
+            ghost = "./assets/images/ghost.png"
+            tank = "./assets/images/tank-tiger-right.png"
+
+            [tilemaps]
+            void = "./assets/tilemaps/void.map"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest.missing_files(&AssetSource::Disk),
+            vec!["./assets/images/ghost.png", "./assets/tilemaps/void.map"]
+        );
+    }
+}
@@ -1,7 +1,188 @@
-use std::fs;
+use std::{cell::RefCell, collections::HashMap, fs, io, ops::Range, rc::Rc, str::FromStr};
 
-#[derive(Builder)]
+use thiserror::Error;
+
+/// Cells without a tile, nothing is drawn for them.
+pub const EMPTY_TILE: u32 = u32::MAX;
+
+/// Lines and columns are 1-based and count from the start of the file, header included.
+#[derive(Debug, Error)]
+pub enum MapError {
+    #[error("failed to read map: {0}")]
+    Io(#[from] io::Error),
+    #[error("map has no header, expected `key = value` lines followed by a blank line")]
+    MissingHeaderEnd,
+    #[error("line {line}: expected `key = value`, got '{text}'")]
+    InvalidHeaderLine { line: usize, text: String },
+    #[error("line {line}: unknown header key '{key}'")]
+    UnknownHeaderKey { line: usize, key: String },
+    #[error("line {line}: invalid value '{value}' for '{key}'")]
+    InvalidHeaderValue {
+        line: usize,
+        key: String,
+        value: String,
+    },
+    #[error("map header is missing '{0}'")]
+    MissingHeaderKey(String),
+    #[error("map has no tile rows")]
+    NoTiles,
+    #[error("line {line}: unknown layer '{name}', expected ground, decoration or overhang")]
+    UnknownLayer { line: usize, name: String },
+    #[error("line {line}: layer has {found} rows, expected {expected} like the first layer")]
+    LayerSize {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("line {line}, column {column}: '{token}' is not a tile index")]
+    InvalidTile {
+        line: usize,
+        column: usize,
+        token: String,
+    },
+    #[error("line {line}: row has {found} tiles, expected {expected} like the first row")]
+    RaggedRow {
+        line: usize,
+        expected: u32,
+        found: u32,
+    },
+    #[error(
+        "line {line}: expected `name = kind x, y` and `; key = value` properties, got '{text}'"
+    )]
+    InvalidObject { line: usize, text: String },
+    #[error("line {line}: object '{name}' is already defined")]
+    DuplicateObject { line: usize, name: String },
+    #[error("cannot save map: {0}")]
+    CannotSave(String),
+    #[error("line {line}: animation frame {tile} is outside the tileset's {tile_count} tiles")]
+    AnimationFrameOutOfRange {
+        line: usize,
+        tile: u32,
+        tile_count: u32,
+    },
+    #[error(
+        "line {line}, column {column}: tile {tile} is outside the tileset's {tile_count} tiles"
+    )]
+    TileOutOfRange {
+        line: usize,
+        column: usize,
+        tile: u32,
+        tile_count: u32,
+    },
+}
+
+/// Where a layer's tiles draw relative to the entities walking over them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerKind {
+    /// Terrain and roads, below everything else.
+    Ground,
+    /// Details on top of the ground, still below entities.
+    Decoration,
+    /// Tree canopies, roofs and the like, drawn above entities.
+    Overhang,
+}
+
+impl LayerKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ground" => Some(Self::Ground),
+            "decoration" => Some(Self::Decoration),
+            "overhang" => Some(Self::Overhang),
+            _ => None,
+        }
+    }
+}
+
+/// How a tile affects vehicles driving over it, set per tileset index in the map header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TileSurface {
+    /// Trees, rocks and walls, nothing drives or flies through.
+    Solid,
+    /// Blocks vehicles just like solid tiles, anything flying passes over.
+    Water,
+    /// Mud and brush, vehicles cross at reduced speed.
+    Slow,
+}
+
+impl TileSurface {
+    pub fn blocks_movement(&self) -> bool {
+        matches!(self, TileSurface::Solid | TileSurface::Water)
+    }
+}
+
+/// Rectangle of cells sharing a surface, in tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileCollider {
+    pub surface: TileSurface,
+    pub column: u32,
+    pub row: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Tiles shown in turn in place of an animated tile, each for its duration in milliseconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileAnimation {
+    pub frames: Vec<u32>,
+    pub durations: Vec<u32>,
+}
+
+impl TileAnimation {
+    /// Tile shown `elapsed_ms` into the level, every cell with the animated tile shows the same frame.
+    pub fn frame_at(&self, elapsed_ms: u64) -> u32 {
+        let length: u64 = self.durations.iter().map(|duration| *duration as u64).sum();
+        let mut time = elapsed_ms % length;
+
+        for (frame, duration) in self.frames.iter().zip(&self.durations) {
+            if time < *duration as u64 {
+                return *frame;
+            }
+            time -= *duration as u64;
+        }
+        *self.frames.last().unwrap()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileLayer {
+    pub kind: LayerKind,
+    /// Tile indices row by row, `EMPTY_TILE` where the layer has no tile.
+    pub tiles: Vec<u32>,
+}
+
+/// Named point where the level spawns an entity of `kind`, its position is in unscaled map pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnPoint {
+    pub name: String,
+    pub kind: String,
+    pub x: f32,
+    pub y: f32,
+    pub properties: HashMap<String, String>,
+}
+
+impl SpawnPoint {
+    /// Parses the property `key`, `default` when the spawn point doesn't set it.
+    pub fn property<T: FromStr>(&self, key: &str, default: T) -> Result<T, String> {
+        match self.properties.get(key) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("spawn point '{}': invalid {key} '{value}'", self.name)),
+            None => Ok(default),
+        }
+    }
+}
+
+/// Tilemap read from a `.map` file, which starts with a `key = value` header describing
+/// the tileset, followed by a blank line and the layers. Each layer starts with its kind in
+/// brackets, e.g. `[ground]`, followed by one comma separated row of tile indices per line,
+/// `-1` leaves a cell empty. Maps with a single ground layer may leave out the `[ground]` line.
+/// An `[objects]` section lists spawn points, one `name = kind x, y` per line optionally
+/// followed by `; key = value` properties.
+#[derive(Debug, Clone, PartialEq, Builder)]
 pub struct Map {
+    /// Asset id of the texture the tile indices refer to.
+    #[builder(setter(into))]
+    pub tileset: String,
     #[builder(default = "32")]
     pub tile_size: u32,
     #[builder(default = "1.0")]
@@ -9,35 +190,878 @@ pub struct Map {
     pub tiles_per_row: u32,
     #[builder(default = "10")]
     pub tiles_per_file_row: u32,
-    pub tiles: Vec<u32>,
+    /// Rows of tiles in the tileset texture, indices must stay below `tiles_per_file_row * tileset_rows`.
+    pub tileset_rows: u32,
+    /// Surfaces of tileset indices, tiles not listed have no effect on movement.
+    #[builder(default)]
+    pub tile_surfaces: HashMap<u32, TileSurface>,
+    /// Animations keyed by the tile placed in the layers, in the header as `animation.21 = 21:300, 30:300`.
+    #[builder(default)]
+    pub tile_animations: HashMap<u32, TileAnimation>,
+    /// Bottom to top, every layer covers the whole map.
+    pub layers: Vec<TileLayer>,
+    #[builder(default)]
+    pub spawns: Vec<SpawnPoint>,
 }
 
-pub fn load_map(map_file: &str) -> Map {
-    let data = fs::read_to_string(map_file).unwrap(); 
-    let rows = data.lines()
-        .collect::<Vec<&str>>();
+/// The asset store owns each loaded map, the level's `Tilemap` and the editor share it.
+pub type SharedMap = Rc<RefCell<Map>>;
 
-    let columns_count = rows.first().unwrap().split(',').count() as u32;
+impl Map {
+    pub fn rows(&self) -> u32 {
+        self.layers
+            .first()
+            .map_or(0, |layer| layer.tiles.len() as u32 / self.tiles_per_row)
+    }
 
-    let tiles = rows
-        .iter()
-        .flat_map(|line| line.split(',').map(|s| s.parse::<u32>().unwrap())).collect();
+    pub fn tile_count(&self) -> u32 {
+        self.tiles_per_file_row * self.tileset_rows
+    }
+
+    /// Size of a tile on screen, in pixels.
+    pub fn scaled_tile_size(&self) -> f32 {
+        self.tile_size as f32 * self.tile_scale
+    }
+
+    /// Surface of the cell, a solid tile on any layer wins over water, and water over slow tiles.
+    pub fn surface_at(&self, cell: usize) -> Option<TileSurface> {
+        self.layers
+            .iter()
+            .filter_map(|layer| self.tile_surfaces.get(layer.tiles.get(cell)?))
+            .min_by_key(|surface| match surface {
+                TileSurface::Solid => 0,
+                TileSurface::Water => 1,
+                TileSurface::Slow => 2,
+            })
+            .copied()
+    }
 
-    MapBuilder::default()
-        .tiles_per_row(columns_count)
-        .tiles(tiles)
-        .tile_scale(2.0)
+    /// Merges cells of the same surface into as few rectangles as possible, row runs first
+    /// and then extended downwards while the rows below match.
+    pub fn colliders(&self) -> Vec<TileCollider> {
+        let (columns, rows) = (self.tiles_per_row, self.rows());
+        let cell = |column: u32, row: u32| (row * columns + column) as usize;
+        let surfaces: Vec<Option<TileSurface>> = (0..(columns * rows) as usize)
+            .map(|i| self.surface_at(i))
+            .collect();
+        let mut used = vec![false; surfaces.len()];
+        let mut colliders = Vec::new();
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let Some(surface) = surfaces[cell(column, row)] else {
+                    continue;
+                };
+                if used[cell(column, row)] {
+                    continue;
+                }
+
+                let matches = |column: u32, row: u32| {
+                    surfaces[cell(column, row)] == Some(surface) && !used[cell(column, row)]
+                };
+
+                let mut width = 1;
+                while column + width < columns && matches(column + width, row) {
+                    width += 1;
+                }
+                let mut height = 1;
+                while row + height < rows
+                    && (column..column + width).all(|x| matches(x, row + height))
+                {
+                    height += 1;
+                }
+
+                for y in row..row + height {
+                    for x in column..column + width {
+                        used[cell(x, y)] = true;
+                    }
+                }
+                colliders.push(TileCollider {
+                    surface,
+                    column,
+                    row,
+                    width,
+                    height,
+                });
+            }
+        }
+        colliders
+    }
+
+    /// Tile to draw for `tile` at `elapsed_ms`, itself unless it's animated.
+    pub fn tile_frame(&self, tile: u32, elapsed_ms: u64) -> u32 {
+        self.tile_animations
+            .get(&tile)
+            .map_or(tile, |animation| animation.frame_at(elapsed_ms))
+    }
+
+    /// Columns and rows of the cells overlapping a view rectangle, in scaled pixels like the camera.
+    pub fn cells_in_view(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    ) -> (Range<u32>, Range<u32>) {
+        let tile = self.scaled_tile_size();
+        let span = |start: i32, length: u32, cells: u32| {
+            let first = (start as f32 / tile).floor().max(0.0) as u32;
+            let end = ((start as f32 + length as f32) / tile).ceil().max(0.0) as u32;
+            first.min(cells)..end.min(cells)
+        };
+        (
+            span(x, width, self.tiles_per_row),
+            span(y, height, self.rows()),
+        )
+    }
+
+    /// Width and height of the whole map on screen, in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        let tile = self.scaled_tile_size();
+        (
+            (self.tiles_per_row as f32 * tile) as u32,
+            (self.rows() as f32 * tile) as u32,
+        )
+    }
+}
+
+pub fn load_map(map_file: &str) -> Result<Map, MapError> {
+    let data = fs::read_to_string(map_file)?;
+    parse_map(&data)
+}
+
+pub fn parse_map(data: &str) -> Result<Map, MapError> {
+    let mut lines = data.lines().enumerate().map(|(i, line)| (i + 1, line));
+    let mut builder = MapBuilder::default();
+    let mut surfaces = HashMap::new();
+    let mut animations = HashMap::new();
+
+    let mut header_ended = false;
+    for (line, text) in lines.by_ref() {
+        if text.trim().is_empty() {
+            header_ended = true;
+            break;
+        }
+        parse_header_line(&mut builder, &mut surfaces, &mut animations, line, text)?;
+    }
+    if !header_ended {
+        return Err(MapError::MissingHeaderEnd);
+    }
+
+    let mut layers: Vec<ParsedLayer> = Vec::new();
+    let mut columns_count = None;
+    let mut spawns: Vec<SpawnPoint> = Vec::new();
+    let mut in_objects = false;
+
+    for (line, text) in lines.filter(|(_, text)| !text.trim().is_empty()) {
+        let section = text
+            .trim()
+            .strip_prefix('[')
+            .and_then(|name| name.strip_suffix(']'));
+        if section.is_some_and(|name| name.trim() == "objects") {
+            in_objects = true;
+            continue;
+        }
+        if let Some(name) = section {
+            in_objects = false;
+            let kind = LayerKind::from_name(name.trim()).ok_or_else(|| MapError::UnknownLayer {
+                line,
+                name: name.trim().to_owned(),
+            })?;
+            layers.push(ParsedLayer::new(kind, line));
+            continue;
+        }
+
+        if in_objects {
+            let spawn = parse_object_line(line, text)?;
+            if spawns.iter().any(|other| other.name == spawn.name) {
+                return Err(MapError::DuplicateObject {
+                    line,
+                    name: spawn.name,
+                });
+            }
+            spawns.push(spawn);
+            continue;
+        }
+
+        if layers.is_empty() {
+            layers.push(ParsedLayer::new(LayerKind::Ground, line));
+        }
+        let layer = layers.last_mut().unwrap();
+
+        let mut column = 1;
+        let mut row_length = 0;
+        for token in text.split(',') {
+            let trimmed = token.trim();
+            let token_column = column + (token.len() - token.trim_start().len());
+            let tile = match trimmed {
+                "-1" => EMPTY_TILE,
+                _ => trimmed.parse::<u32>().map_err(|_| MapError::InvalidTile {
+                    line,
+                    column: token_column,
+                    token: trimmed.to_owned(),
+                })?,
+            };
+
+            layer.tiles.push(tile);
+            layer.positions.push((line, token_column));
+            row_length += 1;
+            column += token.len() + 1;
+        }
+        layer.rows += 1;
+
+        let expected = *columns_count.get_or_insert(row_length);
+        if row_length != expected {
+            return Err(MapError::RaggedRow {
+                line,
+                expected,
+                found: row_length,
+            });
+        }
+    }
+
+    let expected_rows = layers.first().map_or(0, |layer| layer.rows);
+    if expected_rows == 0 {
+        return Err(MapError::NoTiles);
+    }
+    if let Some(layer) = layers.iter().find(|layer| layer.rows != expected_rows) {
+        return Err(MapError::LayerSize {
+            line: layer.line,
+            expected: expected_rows,
+            found: layer.rows,
+        });
+    }
+
+    let (layers, positions): (Vec<_>, Vec<_>) = layers
+        .into_iter()
+        .map(|layer| {
+            let tile_layer = TileLayer {
+                kind: layer.kind,
+                tiles: layer.tiles,
+            };
+            (tile_layer, layer.positions)
+        })
+        .unzip();
+
+    let animation_lines: HashMap<u32, usize> = animations
+        .iter()
+        .map(|(tile, (line, _))| (*tile, *line))
+        .collect();
+    let map = builder
+        .tile_surfaces(surfaces)
+        .tile_animations(
+            animations
+                .into_iter()
+                .map(|(tile, (_, animation))| (tile, animation))
+                .collect(),
+        )
+        .tiles_per_row(columns_count.ok_or(MapError::NoTiles)?)
+        .layers(layers)
+        .spawns(spawns)
         .build()
-        .unwrap()
+        .map_err(|err| match err {
+            MapBuilderError::UninitializedField(field) => {
+                MapError::MissingHeaderKey(header_key(field).to_owned())
+            }
+            MapBuilderError::ValidationError(reason) => MapError::MissingHeaderKey(reason),
+        })?;
+
+    let tile_count = map.tile_count();
+    for (tile, animation) in &map.tile_animations {
+        let out_of_range = std::iter::once(tile)
+            .chain(&animation.frames)
+            .find(|frame| **frame >= tile_count);
+        if let Some(frame) = out_of_range {
+            return Err(MapError::AnimationFrameOutOfRange {
+                line: animation_lines[tile],
+                tile: *frame,
+                tile_count,
+            });
+        }
+    }
+
+    for (layer, positions) in map.layers.iter().zip(&positions) {
+        let out_of_range = layer
+            .tiles
+            .iter()
+            .position(|tile| *tile != EMPTY_TILE && *tile >= tile_count);
+        if let Some(i) = out_of_range {
+            let (line, column) = positions[i];
+            return Err(MapError::TileOutOfRange {
+                line,
+                column,
+                tile: layer.tiles[i],
+                tile_count,
+            });
+        }
+    }
+
+    Ok(map)
+}
+
+pub fn save_map(map: &Map, map_file: &str) -> Result<(), MapError> {
+    let data = serialize_map(map)?;
+    fs::write(map_file, data)?;
+    Ok(())
+}
+
+/// Writes `map` in the format `parse_map` reads, parsing the result gives back an equal map.
+///
+/// Fails for maps the format can't express, like names or property values containing `;`.
+pub fn serialize_map(map: &Map) -> Result<String, MapError> {
+    let cannot_save = |reason: String| Err(MapError::CannotSave(reason));
+    let cells = map.layers.first().map_or(0, |layer| layer.tiles.len());
+    if map.tiles_per_row == 0 || cells == 0 || !cells.is_multiple_of(map.tiles_per_row as usize) {
+        return cannot_save(format!(
+            "{cells} tiles don't make rows of {}",
+            map.tiles_per_row
+        ));
+    }
+    if map.layers.iter().any(|layer| layer.tiles.len() != cells) {
+        return cannot_save("layers have different sizes".to_owned());
+    }
+    if !is_plain(&map.tileset) || map.tileset.is_empty() {
+        return cannot_save(format!("invalid tileset '{}'", map.tileset));
+    }
+
+    let mut out = String::new();
+    out += &format!("tileset = {}\n", map.tileset);
+    out += &format!("tile_size = {}\n", map.tile_size);
+    out += &format!("tile_scale = {:?}\n", map.tile_scale);
+    out += &format!("tileset_columns = {}\n", map.tiles_per_file_row);
+    out += &format!("tileset_rows = {}\n", map.tileset_rows);
+
+    for (key, surface) in [
+        ("solid_tiles", TileSurface::Solid),
+        ("water_tiles", TileSurface::Water),
+        ("slow_tiles", TileSurface::Slow),
+    ] {
+        let mut tiles: Vec<_> = map
+            .tile_surfaces
+            .iter()
+            .filter(|(_, tile_surface)| **tile_surface == surface)
+            .map(|(tile, _)| *tile)
+            .collect();
+        if tiles.is_empty() {
+            continue;
+        }
+        tiles.sort_unstable();
+        out += &format!("{key} = {}\n", join(&tiles));
+    }
+
+    let mut animations: Vec<_> = map.tile_animations.iter().collect();
+    animations.sort_by_key(|(tile, _)| **tile);
+    for (tile, animation) in animations {
+        if animation.frames.is_empty() || animation.frames.len() != animation.durations.len() {
+            return cannot_save(format!("animation of tile {tile} has mismatched frames"));
+        }
+        let frames: Vec<_> = animation
+            .frames
+            .iter()
+            .zip(&animation.durations)
+            .map(|(frame, duration)| format!("{frame}:{duration}"))
+            .collect();
+        out += &format!("animation.{tile} = {}\n", frames.join(", "));
+    }
+
+    for layer in &map.layers {
+        let name = match layer.kind {
+            LayerKind::Ground => "ground",
+            LayerKind::Decoration => "decoration",
+            LayerKind::Overhang => "overhang",
+        };
+        out += &format!("\n[{name}]\n");
+        for row in layer.tiles.chunks(map.tiles_per_row as usize) {
+            let row: Vec<_> = row
+                .iter()
+                .map(|tile| match *tile {
+                    EMPTY_TILE => "-1".to_owned(),
+                    tile => tile.to_string(),
+                })
+                .collect();
+            out += &row.join(",");
+            out += "\n";
+        }
+    }
+
+    if !map.spawns.is_empty() {
+        out += "\n[objects]\n";
+    }
+    for (i, spawn) in map.spawns.iter().enumerate() {
+        let valid_name = is_plain(&spawn.name)
+            && !spawn.name.is_empty()
+            && !spawn.name.starts_with('[')
+            && !spawn.name.contains('=');
+        let valid_kind = is_plain(&spawn.kind)
+            && !spawn.kind.is_empty()
+            && !spawn.kind.contains(char::is_whitespace);
+        if !valid_name || !valid_kind {
+            return cannot_save(format!(
+                "invalid spawn point '{}' of kind '{}'",
+                spawn.name, spawn.kind
+            ));
+        }
+        if map.spawns[..i].iter().any(|other| other.name == spawn.name) {
+            return cannot_save(format!("spawn point '{}' is defined twice", spawn.name));
+        }
+
+        out += &format!(
+            "{} = {} {:?}, {:?}",
+            spawn.name, spawn.kind, spawn.x, spawn.y
+        );
+        let mut properties: Vec<_> = spawn.properties.iter().collect();
+        properties.sort();
+        for (key, value) in properties {
+            if !is_plain(key) || key.is_empty() || key.contains('=') || !is_plain(value) {
+                return cannot_save(format!(
+                    "spawn point '{}' has an invalid property {key} = '{value}'",
+                    spawn.name
+                ));
+            }
+            out += &format!("; {key} = {value}");
+        }
+        out += "\n";
+    }
+
+    Ok(out)
+}
+
+/// Text that reads back unchanged, no separators, line breaks or surrounding whitespace.
+fn is_plain(text: &str) -> bool {
+    text.trim() == text && !text.contains([';', '\n', '\r'])
+}
+
+fn join(tiles: &[u32]) -> String {
+    tiles
+        .iter()
+        .map(|tile| tile.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Layer being read, remembers where each tile came from for error messages.
+struct ParsedLayer {
+    kind: LayerKind,
+    line: usize,
+    rows: usize,
+    tiles: Vec<u32>,
+    positions: Vec<(usize, usize)>,
+}
+
+impl ParsedLayer {
+    fn new(kind: LayerKind, line: usize) -> Self {
+        Self {
+            kind,
+            line,
+            rows: 0,
+            tiles: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+}
+
+fn parse_header_line(
+    builder: &mut MapBuilder,
+    surfaces: &mut HashMap<u32, TileSurface>,
+    animations: &mut HashMap<u32, (usize, TileAnimation)>,
+    line: usize,
+    text: &str,
+) -> Result<(), MapError> {
+    let (key, value) = text
+        .split_once('=')
+        .ok_or_else(|| MapError::InvalidHeaderLine {
+            line,
+            text: text.to_owned(),
+        })?;
+    let (key, value) = (key.trim(), value.trim());
+
+    let invalid = || MapError::InvalidHeaderValue {
+        line,
+        key: key.to_owned(),
+        value: value.to_owned(),
+    };
+
+    match key {
+        "tileset" if !value.is_empty() => builder.tileset(value),
+        "tileset" => return Err(invalid()),
+        "tile_size" => builder.tile_size(value.parse().map_err(|_| invalid())?),
+        "tile_scale" => builder.tile_scale(value.parse().map_err(|_| invalid())?),
+        "tileset_columns" => builder.tiles_per_file_row(value.parse().map_err(|_| invalid())?),
+        "tileset_rows" => builder.tileset_rows(value.parse().map_err(|_| invalid())?),
+        "solid_tiles" | "water_tiles" | "slow_tiles" => {
+            let surface = match key {
+                "solid_tiles" => TileSurface::Solid,
+                "water_tiles" => TileSurface::Water,
+                _ => TileSurface::Slow,
+            };
+            for tile in value.split(',') {
+                let tile = tile.trim().parse().map_err(|_| invalid())?;
+                surfaces.insert(tile, surface);
+            }
+            return Ok(());
+        }
+        _ if key.starts_with("animation.") => {
+            let tile = key["animation.".len()..].parse().map_err(|_| invalid())?;
+            let mut animation = TileAnimation {
+                frames: Vec::new(),
+                durations: Vec::new(),
+            };
+            for frame in value.split(',') {
+                let (frame, duration) = frame.split_once(':').ok_or_else(invalid)?;
+                animation
+                    .frames
+                    .push(frame.trim().parse().map_err(|_| invalid())?);
+                match duration.trim().parse() {
+                    Ok(duration) if duration > 0 => animation.durations.push(duration),
+                    _ => return Err(invalid()),
+                }
+            }
+            animations.insert(tile, (line, animation));
+            return Ok(());
+        }
+        _ => {
+            return Err(MapError::UnknownHeaderKey {
+                line,
+                key: key.to_owned(),
+            })
+        }
+    };
+    Ok(())
+}
+
+/// Reads a spawn point like `tank = enemy 392, 72; velocity_x = 25`.
+fn parse_object_line(line: usize, text: &str) -> Result<SpawnPoint, MapError> {
+    let invalid = || MapError::InvalidObject {
+        line,
+        text: text.trim().to_owned(),
+    };
+
+    let mut parts = text.split(';');
+    let (name, placement) = parts
+        .next()
+        .and_then(|part| part.split_once('='))
+        .ok_or_else(invalid)?;
+    let (kind, position) = placement
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(invalid)?;
+    let (x, y) = position.split_once(',').ok_or_else(invalid)?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(invalid());
+    }
+
+    let properties = parts
+        .map(|property| {
+            let (key, value) = property.split_once('=').ok_or_else(invalid)?;
+            Ok((key.trim().to_owned(), value.trim().to_owned()))
+        })
+        .collect::<Result<_, MapError>>()?;
+
+    Ok(SpawnPoint {
+        name: name.to_owned(),
+        kind: kind.to_owned(),
+        x: x.trim().parse().map_err(|_| invalid())?,
+        y: y.trim().parse().map_err(|_| invalid())?,
+        properties,
+    })
+}
+
+/// Header key that sets a `Map` field, for reporting missing ones.
+fn header_key(field: &str) -> &str {
+    match field {
+        "tiles_per_file_row" => "tileset_columns",
+        field => field,
+    }
+}
+
+/// Map on a 2x2 jungle tileset for tests, with extra `header` lines and the layers in `body`.
+#[cfg(test)]
+pub(crate) fn test_map(header: &str, body: &str) -> Map {
+    parse_map(&format!(
+        "tileset = jungle\ntileset_columns = 2\ntileset_rows = 2\n{header}\n{body}"
+    ))
+    .unwrap()
 }
 
 #[cfg(test)]
 mod test {
-    use super::load_map;
+    use std::{collections::HashMap, env, fs, process};
+
+    use super::{
+        load_map, save_map, serialize_map, test_map, LayerKind, MapBuilder, MapError, SpawnPoint,
+        TileAnimation, TileCollider, TileLayer, TileSurface, EMPTY_TILE,
+    };
+
+    const HEADER: &str = "tileset = jungle\ntileset_columns = 2\ntileset_rows = 2\n\n";
 
     #[test]
     fn parse_map() {
-        let tiles = load_map("./assets/tilemaps/jungle.map");
+        let map = load_map("./assets/tilemaps/jungle.map").unwrap();
+
+        assert_eq!(map.tileset, "jungle");
+        assert_eq!(map.tiles_per_file_row, 10);
+        assert_eq!(map.size(), (1600, 1280));
+
+        assert_eq!(map.layers[0].kind, LayerKind::Ground);
+
+        let map = test_map("", "0, 1,2\n3,2,1\n");
+        assert_eq!(map.layers[0].tiles, vec![0, 1, 2, 3, 2, 1]);
+
+        let map = test_map("", "[ground]\n0,1\n2,3\n\n[overhang]\n-1,3\n-1,-1\n");
+        assert_eq!(map.layers.len(), 2);
+        assert_eq!(map.layers[1].kind, LayerKind::Overhang);
+        assert_eq!(
+            map.layers[1].tiles,
+            vec![EMPTY_TILE, 3, EMPTY_TILE, EMPTY_TILE]
+        );
+
+        assert!(matches!(
+            super::parse_map(&format!("{HEADER}[ground]\n0,1\n2,3\n[overhang]\n1,1\n")),
+            Err(MapError::LayerSize {
+                line: 8,
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            super::parse_map(&format!("{HEADER}[roof]\n0,1\n")),
+            Err(MapError::UnknownLayer { line: 5, name }) if name == "roof"
+        ));
+
+        assert!(matches!(
+            super::parse_map(&format!("{HEADER}0,1,2\n3, x1,1\n")),
+            Err(MapError::InvalidTile { line: 6, column: 4, token }) if token == "x1"
+        ));
+        assert!(matches!(
+            super::parse_map(&format!("{HEADER}0,1,2\n3,2\n")),
+            Err(MapError::RaggedRow {
+                line: 6,
+                expected: 3,
+                found: 2
+            })
+        ));
+        assert!(matches!(
+            super::parse_map(&format!("{HEADER}0,1,2\n3,2,4\n")),
+            Err(MapError::TileOutOfRange {
+                line: 6,
+                column: 5,
+                tile: 4,
+                tile_count: 4
+            })
+        ));
+        assert!(matches!(
+            super::parse_map("tileset = jungle\ntile_size = big\n\n0\n"),
+            Err(MapError::InvalidHeaderValue { line: 2, .. })
+        ));
+        assert!(matches!(
+            super::parse_map("tileset = jungle\ntileset_columns = 2\n\n0\n"),
+            Err(MapError::MissingHeaderKey(key)) if key == "tileset_rows"
+        ));
+        assert!(matches!(
+            super::parse_map("0,1,2\n3,2,1\n"),
+            Err(MapError::InvalidHeaderLine { line: 1, .. })
+        ));
+        assert!(matches!(
+            load_map("./assets/tilemaps/missing.map"),
+            Err(MapError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn reads_spawn_points() {
+        let map = test_map(
+            "",
+            "[ground]\n0,1\n\n[objects]\n\
+             tank = enemy 392, 72; velocity_x = 25; sprite = tank\n\
+             radar = radar 730,20\n\n\
+             [overhang]\n-1,3\n",
+        );
+
+        assert_eq!(map.layers.len(), 2);
+        assert_eq!(map.spawns.len(), 2);
+        let tank = &map.spawns[0];
+        assert_eq!((tank.name.as_str(), tank.kind.as_str()), ("tank", "enemy"));
+        assert_eq!((tank.x, tank.y), (392.0, 72.0));
+        assert_eq!(tank.property("velocity_x", 0.0), Ok(25.0));
+        assert_eq!(tank.property("velocity_y", 0.0), Ok(0.0));
+        assert_eq!(
+            tank.property("sprite", 0),
+            Err("spawn point 'tank': invalid sprite 'tank'".to_owned())
+        );
+        assert!(map.spawns[1].properties.is_empty());
+
+        assert!(matches!(
+            super::parse_map(&format!("{HEADER}0,1\n[objects]\ntank = enemy 392\n")),
+            Err(MapError::InvalidObject { line: 7, .. })
+        ));
+        assert!(matches!(
+            super::parse_map(&format!(
+                "{HEADER}0,1\n[objects]\ntank = enemy 1, 2\ntank = enemy 3, 4\n"
+            )),
+            Err(MapError::DuplicateObject { line: 8, name }) if name == "tank"
+        ));
+    }
+
+    #[test]
+    fn animates_tiles() {
+        let map = test_map("animation.1 = 1:100, 2:50, 3:100\n", "0,1\n");
+
+        assert_eq!(map.tile_frame(0, 120), 0);
+        assert_eq!(map.tile_frame(1, 0), 1);
+        assert_eq!(map.tile_frame(1, 120), 2);
+        assert_eq!(map.tile_frame(1, 150), 3);
+        assert_eq!(map.tile_frame(1, 260), 1);
+
+        assert!(matches!(
+            super::parse_map(
+                "tileset = jungle\ntileset_columns = 2\ntileset_rows = 2\n\
+                 animation.1 = 1:100, 4:100\n\n0,1\n"
+            ),
+            Err(MapError::AnimationFrameOutOfRange {
+                line: 4,
+                tile: 4,
+                tile_count: 4
+            })
+        ));
+        assert!(matches!(
+            super::parse_map(
+                "tileset = jungle\ntileset_columns = 2\ntileset_rows = 2\n\
+                 animation.1 = 1:0\n\n0,1\n"
+            ),
+            Err(MapError::InvalidHeaderValue { line: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn round_trips_maps() {
+        let map = load_map("./assets/tilemaps/jungle.map").unwrap();
+        // Per process, so parallel test runs don't overwrite each other's file
+        let path = env::temp_dir().join(format!("game2d-round-trip-{}.map", process::id()));
+        let path = path.to_str().unwrap();
+        save_map(&map, path).unwrap();
+        let saved = load_map(path);
+        fs::remove_file(path).unwrap();
+        assert_eq!(saved.unwrap(), map);
+
+        let map = MapBuilder::default()
+            .tileset("desert tiles")
+            .tile_size(16)
+            .tile_scale(1.5)
+            .tiles_per_row(2)
+            .tiles_per_file_row(4)
+            .tileset_rows(2)
+            .tile_surfaces(HashMap::from([
+                (1, TileSurface::Water),
+                (2, TileSurface::Solid),
+                (5, TileSurface::Slow),
+            ]))
+            .tile_animations(HashMap::from([(
+                1,
+                TileAnimation {
+                    frames: vec![1, 6, 7],
+                    durations: vec![250, 125, 250],
+                },
+            )]))
+            .layers(vec![
+                TileLayer {
+                    kind: LayerKind::Ground,
+                    tiles: vec![0, 1, 2, 3],
+                },
+                TileLayer {
+                    kind: LayerKind::Overhang,
+                    tiles: vec![EMPTY_TILE, 5, EMPTY_TILE, EMPTY_TILE],
+                },
+                TileLayer {
+                    kind: LayerKind::Decoration,
+                    tiles: vec![EMPTY_TILE; 4],
+                },
+            ])
+            .spawns(vec![SpawnPoint {
+                name: "truck 1".to_owned(),
+                kind: "enemy".to_owned(),
+                x: 10.25,
+                y: -3.0,
+                properties: HashMap::from([
+                    ("velocity_x".to_owned(), "-50".to_owned()),
+                    ("label".to_owned(), "a = b".to_owned()),
+                ]),
+            }])
+            .build()
+            .unwrap();
+        let data = serialize_map(&map).unwrap();
+        assert_eq!(super::parse_map(&data).unwrap(), map);
+        assert_eq!(
+            serialize_map(&super::parse_map(&data).unwrap()).unwrap(),
+            data
+        );
+
+        let mut unsaveable = map;
+        unsaveable.spawns[0]
+            .properties
+            .insert("note".to_owned(), "one; two".to_owned());
+        assert!(matches!(
+            serialize_map(&unsaveable),
+            Err(MapError::CannotSave(_))
+        ));
+    }
+
+    #[test]
+    fn merges_tile_colliders() {
+        let map = test_map(
+            "water_tiles = 1\nsolid_tiles = 2\n",
+            "[ground]\n1,1,0\n1,1,0\n0,2,2\n\
+             [decoration]\n-1,-1,-1\n-1,2,-1\n-1,-1,-1\n",
+        );
+
+        assert_eq!(map.surface_at(4), Some(TileSurface::Solid));
+        assert_eq!(
+            map.colliders(),
+            vec![
+                TileCollider {
+                    surface: TileSurface::Water,
+                    column: 0,
+                    row: 0,
+                    width: 2,
+                    height: 1
+                },
+                TileCollider {
+                    surface: TileSurface::Water,
+                    column: 0,
+                    row: 1,
+                    width: 1,
+                    height: 1
+                },
+                TileCollider {
+                    surface: TileSurface::Solid,
+                    column: 1,
+                    row: 1,
+                    width: 1,
+                    height: 2
+                },
+                TileCollider {
+                    surface: TileSurface::Solid,
+                    column: 2,
+                    row: 2,
+                    width: 1,
+                    height: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_cells_in_view() {
+        let row = "0,0,0,0,0,0";
+        let map = test_map(
+            "tile_size = 16\ntile_scale = 2.0\n",
+            &format!("[ground]\n{row}\n{row}\n{row}\n{row}\n"),
+        );
 
+        assert_eq!(map.cells_in_view(0, 0, 64, 64), (0..2, 0..2));
+        assert_eq!(map.cells_in_view(40, 70, 64, 32), (1..4, 2..4));
+        assert_eq!(map.cells_in_view(-50, -50, 100, 60), (0..2, 0..1));
+        assert_eq!(map.cells_in_view(150, 0, 200, 500), (4..6, 0..4));
+        assert_eq!(map.cells_in_view(500, 500, 64, 64), (6..6, 4..4));
     }
 }
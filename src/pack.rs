@@ -0,0 +1,232 @@
+//! Single-file archive of the assets tree.
+//!
+//! Layout, integers are little endian: the `GPAK` magic, format version (u32) and entry
+//! count (u32), then an index entry per file with its path length (u16), UTF-8 path,
+//! blob offset (u64), blob size (u64) and CRC32 (u32), followed by the blobs themselves.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+pub const PACK_FILE: &str = "./assets.pak";
+
+const MAGIC: &[u8; 4] = b"GPAK";
+const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 12;
+const ENTRY_FIXED_SIZE: u64 = 2 + 8 + 8 + 4;
+
+#[derive(Debug, Error)]
+pub enum PackError {
+    #[error("asset pack io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("'{0}' is not an asset pack")]
+    NotAPack(String),
+    #[error("unsupported asset pack version {0}")]
+    Version(u32),
+    #[error("'{0}' is not in the asset pack")]
+    NotFound(String),
+    #[error("'{0}' failed its checksum")]
+    Checksum(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackEntry {
+    pub offset: u64,
+    pub size: u64,
+    pub checksum: u32,
+}
+
+pub struct AssetPack {
+    path: PathBuf,
+    entries: BTreeMap<String, PackEntry>,
+}
+
+impl AssetPack {
+    /// Reads the index, blobs are only read on demand.
+    pub fn open(path: &Path) -> Result<Self, PackError> {
+        let mut file = File::open(path)?;
+
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(PackError::NotAPack(path.display().to_string()));
+        }
+
+        let version = read_u32(&mut file)?;
+        if version != VERSION {
+            return Err(PackError::Version(version));
+        }
+
+        let count = read_u32(&mut file)?;
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            let mut path_len = [0; 2];
+            file.read_exact(&mut path_len)?;
+            let mut asset_path = vec![0; u16::from_le_bytes(path_len) as usize];
+            file.read_exact(&mut asset_path)?;
+            let asset_path = String::from_utf8(asset_path)
+                .map_err(|_| PackError::NotAPack(path.display().to_string()))?;
+
+            let entry = PackEntry {
+                offset: read_u64(&mut file)?,
+                size: read_u64(&mut file)?,
+                checksum: read_u32(&mut file)?,
+            };
+            entries.insert(asset_path, entry);
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+            entries,
+        })
+    }
+
+    pub fn contains(&self, asset_path: &str) -> bool {
+        self.entries.contains_key(&normalize(asset_path))
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    pub fn read(&self, asset_path: &str) -> Result<Vec<u8>, PackError> {
+        let asset_path = normalize(asset_path);
+        let entry = self
+            .entries
+            .get(&asset_path)
+            .ok_or_else(|| PackError::NotFound(asset_path.clone()))?;
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut data = vec![0; entry.size as usize];
+        file.read_exact(&mut data)?;
+
+        if crc32fast::hash(&data) != entry.checksum {
+            return Err(PackError::Checksum(asset_path));
+        }
+        Ok(data)
+    }
+
+    /// Reads every blob back and returns the failures.
+    pub fn verify(&self) -> Vec<PackError> {
+        self.entries
+            .keys()
+            .filter_map(|asset_path| self.read(asset_path).err())
+            .collect()
+    }
+}
+
+/// Packs every file under `root`, keyed by its path as seen from the working directory.
+pub fn build_pack(root: &Path, output: &Path) -> Result<usize, PackError> {
+    let mut files = Vec::new();
+    collect_files(root, &mut files)?;
+    files.sort();
+
+    let blobs = files
+        .iter()
+        .map(|file| Ok((normalize(&file.to_string_lossy()), fs::read(file)?)))
+        .collect::<Result<Vec<_>, PackError>>()?;
+
+    let index_size: u64 = blobs
+        .iter()
+        .map(|(asset_path, _)| ENTRY_FIXED_SIZE + asset_path.len() as u64)
+        .sum();
+
+    let mut out = io::BufWriter::new(File::create(output)?);
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&(blobs.len() as u32).to_le_bytes())?;
+
+    let mut offset = HEADER_SIZE + index_size;
+    for (asset_path, data) in &blobs {
+        out.write_all(&(asset_path.len() as u16).to_le_bytes())?;
+        out.write_all(asset_path.as_bytes())?;
+        out.write_all(&offset.to_le_bytes())?;
+        out.write_all(&(data.len() as u64).to_le_bytes())?;
+        out.write_all(&crc32fast::hash(data).to_le_bytes())?;
+        offset += data.len() as u64;
+    }
+
+    for (_, data) in &blobs {
+        out.write_all(data)?;
+    }
+    out.flush()?;
+
+    Ok(blobs.len())
+}
+
+/// Pack keys use forward slashes without a leading `./`, so `./assets/x.png` finds `assets/x.png`.
+pub fn normalize(asset_path: &str) -> String {
+    asset_path
+        .replace('\\', "/")
+        .trim_start_matches("./")
+        .to_owned()
+}
+
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{build_pack, AssetPack, PackError};
+
+    #[test]
+    fn round_trips_and_detects_corruption() {
+        let root = std::env::temp_dir().join(format!("game2d-pack-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("assets/maps")).unwrap();
+        fs::write(root.join("assets/a.txt"), b"first").unwrap();
+        fs::write(root.join("assets/maps/b.map"), b"1,2,3\n4,5,6").unwrap();
+
+        let pack_file = root.join("test.pak");
+        let count = build_pack(&root.join("assets"), &pack_file).unwrap();
+        assert_eq!(count, 2);
+
+        let pack = AssetPack::open(&pack_file).unwrap();
+        let map_path = format!("{}/maps/b.map", root.join("assets").display());
+        assert_eq!(pack.read(&map_path).unwrap(), b"1,2,3\n4,5,6");
+        assert!(pack.verify().is_empty());
+        assert!(matches!(
+            pack.read("assets/missing.png"),
+            Err(PackError::NotFound(_))
+        ));
+
+        let mut bytes = fs::read(&pack_file).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        fs::write(&pack_file, bytes).unwrap();
+
+        let pack = AssetPack::open(&pack_file).unwrap();
+        assert_eq!(pack.verify().len(), 1);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
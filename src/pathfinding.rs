@@ -0,0 +1,209 @@
+//! A* over the tiles of a map, vehicles can't cross water or solid tiles and avoid slow ones.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::map::{Map, TileSurface};
+
+/// Cost of moving to a side neighbour of an ordinary cell, diagonal steps cost `DIAGONAL_COST`.
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+/// Slow tiles halve the speed, so crossing one takes twice as long.
+const SLOW_COST_FACTOR: u32 = 2;
+
+pub type Cell = (u32, u32);
+
+/// Walkability and movement cost of every cell of the level map, kept as a resource.
+#[derive(Debug, Clone)]
+pub struct NavGrid {
+    columns: u32,
+    rows: u32,
+    tile_size: f32,
+    /// Cost factor per cell, `None` where vehicles can't go.
+    costs: Vec<Option<u32>>,
+}
+
+impl NavGrid {
+    pub fn from_map(map: &Map) -> Self {
+        let cells = (map.tiles_per_row * map.rows()) as usize;
+        let costs = (0..cells)
+            .map(|cell| match map.surface_at(cell) {
+                Some(surface) if surface.blocks_movement() => None,
+                Some(TileSurface::Slow) => Some(SLOW_COST_FACTOR),
+                _ => Some(1),
+            })
+            .collect();
+
+        Self {
+            columns: map.tiles_per_row,
+            rows: map.rows(),
+            tile_size: map.scaled_tile_size(),
+            costs,
+        }
+    }
+
+    fn cost(&self, (column, row): Cell) -> Option<u32> {
+        if column >= self.columns || row >= self.rows {
+            return None;
+        }
+        self.costs[(row * self.columns + column) as usize]
+    }
+
+    pub fn is_walkable(&self, cell: Cell) -> bool {
+        self.cost(cell).is_some()
+    }
+
+    /// Cell containing a position in scaled pixels, `None` outside the map.
+    pub fn cell_at(&self, x: f32, y: f32) -> Option<Cell> {
+        let (column, row) = ((x / self.tile_size).floor(), (y / self.tile_size).floor());
+        (column >= 0.0 && row >= 0.0 && (column as u32) < self.columns && (row as u32) < self.rows)
+            .then_some((column as u32, row as u32))
+    }
+
+    pub fn cell_center(&self, (column, row): Cell) -> (f32, f32) {
+        (
+            (column as f32 + 0.5) * self.tile_size,
+            (row as f32 + 0.5) * self.tile_size,
+        )
+    }
+
+    /// Cheapest path from `start` to `goal`, without `start` and ending with `goal`, so it is
+    /// just `goal` when both are the same cell.
+    ///
+    /// Diagonal steps are only taken when both side cells are walkable, so paths never cut
+    /// the corner of a blocked cell. `None` when the goal is blocked or can't be reached.
+    pub fn find_path(&self, start: Cell, goal: Cell) -> Option<Vec<Cell>> {
+        if !self.is_walkable(goal) || start.0 >= self.columns || start.1 >= self.rows {
+            return None;
+        }
+
+        let index = |(column, row): Cell| (row * self.columns + column) as usize;
+        let cell = |index: usize| (index as u32 % self.columns, index as u32 / self.columns);
+        let heuristic = |(column, row): Cell| {
+            let dx = column.abs_diff(goal.0);
+            let dy = row.abs_diff(goal.1);
+            STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+        };
+
+        let mut costs = vec![u32::MAX; self.costs.len()];
+        let mut came_from = vec![None; self.costs.len()];
+        let mut open = BinaryHeap::new();
+        costs[index(start)] = 0;
+        open.push(Reverse((heuristic(start), 0, index(start))));
+
+        while let Some(Reverse((_, cost, current))) = open.pop() {
+            if cell(current) == goal {
+                let mut path = vec![goal];
+                let mut step = current;
+                while let Some(previous) = came_from[step] {
+                    if previous == index(start) {
+                        break;
+                    }
+                    path.push(cell(previous));
+                    step = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if cost > costs[current] {
+                continue;
+            }
+
+            let (column, row) = cell(current);
+            for (dx, dy) in [
+                (-1, 0),
+                (1, 0),
+                (0, -1),
+                (0, 1),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ] {
+                let (Some(x), Some(y)) =
+                    (column.checked_add_signed(dx), row.checked_add_signed(dy))
+                else {
+                    continue;
+                };
+                let Some(factor) = self.cost((x, y)) else {
+                    continue;
+                };
+                let diagonal = dx != 0 && dy != 0;
+                if diagonal && !(self.is_walkable((x, row)) && self.is_walkable((column, y))) {
+                    continue;
+                }
+
+                let step = if diagonal {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+                let next_cost = cost + step * factor;
+                let next = index((x, y));
+                if next_cost < costs[next] {
+                    costs[next] = next_cost;
+                    came_from[next] = Some(current);
+                    open.push(Reverse((next_cost + heuristic((x, y)), next_cost, next)));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::NavGrid;
+    use crate::map::test_map;
+
+    fn grid(rows: &str) -> NavGrid {
+        NavGrid::from_map(&test_map(
+            "tile_size = 16\ntile_scale = 2.0\nwater_tiles = 1\nslow_tiles = 2\n",
+            rows,
+        ))
+    }
+
+    #[test]
+    fn finds_paths_around_water_and_slow_tiles() {
+        let nav = grid(
+            "0,0,0,0,0\n\
+             0,1,1,1,0\n\
+             0,0,0,1,0\n\
+             2,2,0,1,0\n\
+             0,0,0,0,0\n",
+        );
+
+        assert_eq!(nav.cell_at(40.0, 70.0), Some((1, 2)));
+        assert_eq!(nav.cell_at(-1.0, 0.0), None);
+        assert_eq!(nav.cell_center((1, 2)), (48.0, 80.0));
+
+        assert_eq!(
+            nav.find_path((0, 2), (4, 2)),
+            Some(vec![(1, 2), (2, 3), (2, 4), (3, 4), (4, 4), (4, 3), (4, 2)])
+        );
+        assert_eq!(nav.find_path((0, 2), (0, 4)), Some(vec![(0, 3), (0, 4)]));
+        assert_eq!(nav.find_path((0, 0), (2, 1)), None);
+        assert_eq!(nav.find_path((0, 0), (0, 0)), Some(vec![(0, 0)]));
+    }
+
+    #[test]
+    fn does_not_cut_corners_or_cross_closed_walls() {
+        let nav = grid(
+            "0,1,0\n\
+             1,0,0\n\
+             0,0,0\n",
+        );
+        assert_eq!(nav.find_path((0, 0), (1, 1)), None);
+        assert_eq!(
+            nav.find_path((2, 0), (0, 2)),
+            Some(vec![(2, 1), (1, 2), (0, 2)])
+        );
+
+        let nav = grid("0,2,2,2,0\n0,0,0,0,0\n");
+        assert_eq!(
+            nav.find_path((0, 0), (4, 0)),
+            Some(vec![(1, 1), (2, 1), (3, 1), (4, 0)])
+        );
+    }
+}
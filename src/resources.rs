@@ -1,3 +1,28 @@
 use time::Duration;
 
+use crate::asset_store::TextureHandle;
+use crate::map::SharedMap;
+
 pub struct DeltaTime(pub Duration);
+
+#[derive(Debug, Clone, Default)]
+pub struct LoadingProgress {
+    pub loaded: usize,
+    pub total: usize,
+}
+
+impl LoadingProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.loaded as f32 / self.total as f32
+        }
+    }
+}
+
+/// Level map drawn by `RenderSystem`, ground and decoration below entities and overhang above them.
+pub struct Tilemap {
+    pub map: SharedMap,
+    pub tileset: TextureHandle,
+}
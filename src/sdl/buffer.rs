@@ -1,5 +1,7 @@
 use std::mem::size_of;
 
+use image::{DynamicImage, ImageResult};
+
 #[derive(Clone, Debug, Default, PartialEq, PartialOrd)]
 pub struct PixelBuffer {
     pub pixels: Vec<u8>,
@@ -26,14 +28,20 @@ impl PixelBuffer {
         self.width
     }
 
-    // pub fn load_texture(name: String) -> Self {
-    //     let image = image::open(format!("./images/{}", name)).unwrap();
-    //     PixelBuffer::from_texture::<u32>(
-    //         image.as_bytes(),
-    //         image.width() as usize,
-    //         image.height() as usize,
-    //     )
-    // }
+    /// Decodes an image file into the buffer's B, G, R, A byte order without needing a renderer.
+    pub fn load_texture(path: &str) -> ImageResult<Self> {
+        let image = image::open(path)?;
+        Ok(PixelBuffer::from_image(&image))
+    }
+
+    pub fn from_image(image: &DynamicImage) -> Self {
+        let mut pixels = image.to_rgba8().into_raw();
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+
+        PixelBuffer::from_texture::<u32>(&pixels, image.width() as usize, image.height() as usize)
+    }
 
     pub fn from_texture<T>(texture: &[u8], width: usize, height: usize) -> Self {
         Self {
@@ -134,3 +142,36 @@ impl PixelBuffer {
         self.pixels[index + 3] = bytes[3]; // ALPHA
     }
 }
+
+#[cfg(test)]
+mod test {
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use super::PixelBuffer;
+
+    #[test]
+    fn decodes_into_bgra_order() {
+        let mut image = RgbaImage::new(2, 2);
+        image.put_pixel(1, 0, Rgba([10, 20, 30, 255]));
+        image.put_pixel(0, 1, Rgba([200, 100, 50, 128]));
+
+        let buffer = PixelBuffer::from_image(&DynamicImage::ImageRgba8(image));
+
+        assert_eq!(
+            (buffer.width(), buffer.height(), buffer.get_pitch()),
+            (2, 2, 8)
+        );
+        assert_eq!(&buffer.pixels[4..8], &[30, 20, 10, 255]);
+        assert_eq!(buffer.get_pixel_at(1, 0), 0xFF0A141E);
+        assert_eq!(buffer.get_pixel_at(0, 1), 0x80C86432);
+        assert_eq!(buffer.get_pixel_at(1, 1), 0x00000000);
+    }
+
+    #[test]
+    fn loads_png_from_disk() {
+        let buffer = PixelBuffer::load_texture("./assets/images/tank-tiger-right.png").unwrap();
+
+        assert_eq!((buffer.width(), buffer.height()), (32, 32));
+        assert_eq!(buffer.pixels.len(), 32 * 32 * 4);
+    }
+}
@@ -2,18 +2,28 @@ use std::{cell::RefCell, rc::Rc};
 
 use sdl2::{
     render::{WindowCanvas, TextureCreator},
-    Sdl, image::InitFlag, video::WindowContext,
+    Sdl, image::InitFlag, video::WindowContext, ttf::Sdl2TtfContext,
 };
 use time::{Duration, Instant};
 
+use crate::audio::Audio;
+
 pub mod buffer;
 
 pub const FPS: i32 = 60;
 pub const MILLIS_PER_FRAME: i32 = 1000 / FPS;
 
+/// SDL refuses to initialise on a second thread while it's open on another, tests that open it
+/// take this lock so they don't run at the same time.
+#[cfg(test)]
+pub static SDL_TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
 pub struct Context {
     pub sdl: Sdl,
     pub canvas: Rc<RefCell<WindowCanvas>>,
+    pub ttf: &'static Sdl2TtfContext,
+    /// `None` when no audio device could be opened, the game then runs silently.
+    _audio: Option<Audio>,
     pub instant: Rc<RefCell<Instant>>,
     pub ticks_last_frame: Duration,
 }
@@ -23,6 +33,8 @@ impl Context {
         let sdl = sdl2::init().unwrap();
         let video_subsystem = sdl.video().unwrap();
         let _image_context = sdl2::image::init(InitFlag::PNG | InitFlag::JPG).unwrap();
+        // Fonts borrow the TTF context, leaking it lets them live in the asset store for the whole game
+        let ttf: &'static Sdl2TtfContext = Box::leak(Box::new(sdl2::ttf::init().unwrap()));
         let window = video_subsystem
             .window(name, height, width)
             .position_centered()
@@ -31,12 +43,16 @@ impl Context {
             .build()
             .unwrap();
 
+        let audio = Audio::new(&sdl).ok();
+
         let mut canvas = window.into_canvas().build().unwrap();
         canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
 
         Self {
             sdl: sdl,
             canvas: Rc::new(RefCell::new(canvas)),
+            ttf,
+            _audio: audio,
             instant: Rc::new(RefCell::new(Instant::now())),
             ticks_last_frame: Duration::milliseconds(0),
         }
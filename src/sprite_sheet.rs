@@ -0,0 +1,147 @@
+use std::{collections::HashMap, path::Path};
+
+use sdl2::rect::Rect;
+use serde::Deserialize;
+
+/// Frame layout and named animation clips of a texture, read from a `.sheet.toml` file next to it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpriteSheet {
+    pub frames: Vec<Frame>,
+    #[serde(default)]
+    pub clips: HashMap<String, Clip>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Frame {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Frame {
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height)
+    }
+}
+
+/// Plays `frames`, indices into the sheet's frames, showing each for its duration in milliseconds.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Clip {
+    pub frames: Vec<usize>,
+    pub durations: Vec<u32>,
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+impl Clip {
+    /// Index into the sheet's frames shown `elapsed_ms` after the clip started.
+    ///
+    /// Clips that don't loop hold their last frame.
+    pub fn frame_at(&self, elapsed_ms: u64) -> usize {
+        let length: u64 = self.durations.iter().map(|duration| *duration as u64).sum();
+        let mut time = if self.looping {
+            elapsed_ms % length
+        } else {
+            elapsed_ms.min(length - 1)
+        };
+
+        for (frame, duration) in self.frames.iter().zip(&self.durations) {
+            if time < *duration as u64 {
+                return *frame;
+            }
+            time -= *duration as u64;
+        }
+        *self.frames.last().unwrap()
+    }
+}
+
+impl SpriteSheet {
+    pub fn clip(&self, name: &str) -> Option<&Clip> {
+        self.clips.get(name)
+    }
+}
+
+/// `./assets/images/chopper.png` is described by `./assets/images/chopper.sheet.toml`.
+pub fn sprite_sheet_path(texture_path: &str) -> String {
+    Path::new(texture_path)
+        .with_extension("sheet.toml")
+        .to_string_lossy()
+        .into_owned()
+}
+
+pub fn parse_sprite_sheet(data: &str) -> Result<SpriteSheet, String> {
+    let sheet: SpriteSheet = toml::from_str(data).map_err(|err| err.to_string())?;
+
+    for (name, clip) in &sheet.clips {
+        if clip.frames.is_empty() {
+            return Err(format!("clip '{name}' has no frames"));
+        }
+        if clip.frames.len() != clip.durations.len() {
+            return Err(format!(
+                "clip '{name}' has {} frames but {} durations",
+                clip.frames.len(),
+                clip.durations.len()
+            ));
+        }
+        if clip.durations.contains(&0) {
+            return Err(format!("clip '{name}' has a zero frame duration"));
+        }
+        if let Some(frame) = clip
+            .frames
+            .iter()
+            .find(|frame| **frame >= sheet.frames.len())
+        {
+            return Err(format!(
+                "clip '{name}' uses frame {frame} but the sheet has {} frames",
+                sheet.frames.len()
+            ));
+        }
+    }
+
+    Ok(sheet)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use sdl2::rect::Rect;
+
+    use super::{parse_sprite_sheet, sprite_sheet_path};
+
+    #[test]
+    fn plays_chopper_clips() {
+        let path = sprite_sheet_path("./assets/images/chopper-spritesheet.png");
+        let sheet = parse_sprite_sheet(&fs::read_to_string(path).unwrap()).unwrap();
+        let frame = |name, elapsed_ms| {
+            let clip = sheet.clip(name)?;
+            Some(sheet.frames[clip.frame_at(elapsed_ms)].rect())
+        };
+
+        assert_eq!(frame("fly_up", 0), Some(Rect::new(0, 96, 32, 32)));
+        assert_eq!(frame("fly_up", 70), Some(Rect::new(32, 96, 32, 32)));
+        assert_eq!(frame("fly_up", 140), Some(Rect::new(0, 96, 32, 32)));
+        assert_eq!(frame("hover", 0), None);
+    }
+
+    #[test]
+    fn rejects_clips_outside_the_sheet() {
+        let err = parse_sprite_sheet(
+            r#"
+            frames = [{ x = 0, y = 0, width = 16, height = 16 }]
+
+            [clips.spin]
+            frames = [0, 1]
+            durations = [100, 100]
+            "#,
+        )
+        .unwrap_err();
+
+        assert_eq!(err, "clip 'spin' uses frame 1 but the sheet has 1 frames");
+    }
+}
@@ -1,6 +1,8 @@
 use secs::ecs_macro::GameEvent;
 use sdl2::keyboard::Keycode;
 
+use crate::components::Faction;
+
 
 #[derive(GameEvent)]
 pub struct Collision {
@@ -12,3 +14,17 @@ pub struct Collision {
 pub struct KeyPressed {
     pub key: Keycode
 }
+
+/// An entity ran out of hit points, it's removed with the command buffer after the event so
+/// subscribers can still read its components.
+#[derive(GameEvent)]
+pub struct EntityDied {
+    pub entity: usize,
+    pub faction: Faction,
+}
+
+/// The game is about to load another level, everything spawned for `level` goes away.
+#[derive(GameEvent)]
+pub struct LevelUnloaded {
+    pub level: i32,
+}
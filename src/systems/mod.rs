@@ -1,19 +1,30 @@
+use std::collections::{HashMap, HashSet};
 use std::{cell::RefCell, rc::Rc};
 
+use glam::Vec2;
 use sdl2::keyboard::Keycode;
+use sdl2::mixer::{Channel, Chunk};
 use sdl2::pixels;
 use sdl2::rect::Rect;
-use sdl2::render::WindowCanvas;
+use sdl2::render::{Texture, WindowCanvas};
 use time::Instant;
 
-use self::events::{Collision, KeyPressed};
-use crate::asset_store::AssetStore;
+use self::events::{Collision, EntityDied, KeyPressed, LevelUnloaded};
+use crate::asset_store::{AssetId, AssetKey, AssetStore, SharedAssetStore, TextureHandle};
+use crate::audio::{play_chunk, play_one_shot};
+use crate::editor::TilemapEditor;
 use crate::components::{
-    AnimationComponent, BoxColliderComponent, KeyboardControlledComponent,
-    SpriteLayer,
+    AirborneComponent, AnimationComponent, AudioSourceComponent, BoxColliderComponent,
+    BoxColliderComponentBuilder, DamageComponent, DamageComponentBuilder, HealthComponent,
+    KeyboardControlledComponent, PathFollowComponent, ProjectileComponent,
+    ProjectileEmitterComponent, SpriteLayer, StaticColliderComponent, TextLabelComponent,
+    TransformComponentBuilder,
 };
 use crate::game::{self, Camera, MapDimensions};
-use crate::resources::DeltaTime;
+use crate::hot_reload::{AssetWatcher, POLL_INTERVAL, WATCHED_DIRECTORIES};
+use crate::map::{LayerKind, SharedMap, TileSurface, EMPTY_TILE};
+use crate::pathfinding::NavGrid;
+use crate::resources::{DeltaTime, LoadingProgress, Tilemap};
 use crate::{
     components::{RigidBodyComponent, SpriteComponent, TransformComponent},
     logger::Logger,
@@ -28,6 +39,8 @@ pub mod events;
 #[derive(Clone, Copy)]
 pub struct MovementSystem;
 
+const SLOW_SURFACE_SPEED: f32 = 0.5;
+
 impl MovementSystem {
     pub fn action(
     _: &mut MovementSystem,
@@ -38,6 +51,9 @@ impl MovementSystem {
     ) {
         let mut transforms = query.components().get_mut::<TransformComponent>();
         let rigid_bodies = query.components().get::<RigidBodyComponent>();
+        let box_colliders = query.components().get::<BoxColliderComponent>();
+        let static_colliders = query.components().get::<StaticColliderComponent>();
+        let airborne = query.components().get::<AirborneComponent>();
         let delta_time = query
             .resources
             .get::<DeltaTime>()
@@ -53,11 +69,37 @@ impl MovementSystem {
             entities.len()
         ));
 
+        let slow_tiles: Vec<(Vec2, &BoxColliderComponent)> = static_colliders
+            .iter()
+            .enumerate()
+            .filter_map(|(id, wall)| {
+                if wall.as_ref()?.surface != TileSurface::Slow {
+                    return None;
+                }
+                let collider = box_colliders.get(id)?;
+                Some((transforms.get(id)?.position + collider.offset, collider))
+            })
+            .collect();
+
         for ent in entities {
             let transform = transforms.get_mut(ent.0).unwrap();
             let rigid_body = rigid_bodies.get(ent.0).unwrap();
 
-            transform.position += rigid_body.velocity * delta_time.as_seconds_f32();
+            // The tiles under the entity before it moves set its speed, flyers pass over slow ones
+            let on_slow_tile = airborne.get(ent.0).is_none()
+                && box_colliders.get(ent.0).is_some_and(|collider| {
+                    let position = transform.position + collider.offset;
+                    slow_tiles.iter().any(|(tile_position, tile)| {
+                        aabb_overlap(position, collider, *tile_position, tile).is_some()
+                    })
+                });
+            let surface_speed = if on_slow_tile {
+                SLOW_SURFACE_SPEED
+            } else {
+                1.0
+            };
+
+            transform.position += rigid_body.velocity * surface_speed * delta_time.as_seconds_f32();
 
             logger.info(&format!(
                 "Entity {} new position is now ({}, {})",
@@ -66,8 +108,144 @@ impl MovementSystem {
         }
     }
 }
+/// Seconds without progress after which a path follower plans a new path.
+const REPLAN_AFTER: f32 = 0.5;
+/// Share of its speed an entity has to cover each frame to count as moving.
+const MIN_PROGRESS: f32 = 0.25;
+/// Distance to a waypoint at which it counts as reached, in pixels, or the distance covered
+/// in the frame when that is longer so fast entities don't overshoot it.
+const WAYPOINT_RADIUS: f32 = 2.0;
+
+/// Steers entities with a `PathFollowComponent` by setting their velocity towards the next waypoint.
+///
+/// Paths come from the `NavGrid` resource. An entity plans again when a tile of its path
+/// stopped being walkable, after a map reload, or when something keeps it from moving.
+pub struct PathFollowSystem;
+
+impl PathFollowSystem {
+    pub fn action(
+        _: &mut PathFollowSystem,
+        query: Query,
+        entities: &[Entity],
+        _: &mut CommandBuffer,
+        _: EventEmitter,
+    ) {
+        let transforms = query.components().get::<TransformComponent>();
+        let box_colliders = query.components().get::<BoxColliderComponent>();
+        let mut rigid_bodies = query.components().get_mut::<RigidBodyComponent>();
+        let mut followers = query.components().get_mut::<PathFollowComponent>();
+        let delta_time = query
+            .resources
+            .get::<DeltaTime>()
+            .borrow()
+            .get::<DeltaTime>()
+            .0
+            .as_seconds_f32();
+
+        let nav_grid_r = query.resources.get::<NavGrid>().borrow();
+        let nav_grid = nav_grid_r.get::<NavGrid>();
+
+        for entity in entities {
+            let transform = transforms.get(entity.0).unwrap();
+            let rigid_body = rigid_bodies.get_mut(entity.0).unwrap();
+            let follower = followers.get_mut(entity.0).unwrap();
+
+            let center = entity_center(transform, box_colliders.get(entity.0));
+            let reach = WAYPOINT_RADIUS.max(follower.speed * delta_time);
+
+            if center.distance(follower.goal) <= reach {
+                follower.path.clear();
+                follower.stuck_time = 0.0;
+                rigid_body.velocity = Vec2::ZERO;
+                continue;
+            }
+
+            // Nothing to compare against on the first frame, which plans right away.
+            if let Some(last) = follower.last_position.replace(transform.position) {
+                if transform.position.distance(last) < follower.speed * delta_time * MIN_PROGRESS {
+                    follower.stuck_time += delta_time;
+                } else {
+                    follower.stuck_time = 0.0;
+                }
+            }
+
+            let blocked = follower.path.iter().any(|waypoint| {
+                !nav_grid
+                    .cell_at(waypoint.x, waypoint.y)
+                    .is_some_and(|cell| nav_grid.is_walkable(cell))
+            });
+            if blocked || !follower.planned || follower.stuck_time >= REPLAN_AFTER {
+                follower.planned = true;
+                follower.stuck_time = 0.0;
+                follower.path = plan_path(nav_grid, center, follower.goal);
+            }
+
+            while follower
+                .path
+                .first()
+                .is_some_and(|waypoint| waypoint.distance(center) <= reach)
+            {
+                follower.path.remove(0);
+            }
+
+            rigid_body.velocity = follower.path.first().map_or(Vec2::ZERO, |waypoint| {
+                (*waypoint - center).normalize_or_zero() * follower.speed
+            });
+        }
+    }
+}
+
+/// Center of the entity's collider, its position when it has none.
+fn entity_center(transform: &TransformComponent, collider: Option<&BoxColliderComponent>) -> Vec2 {
+    transform.position
+        + collider.map_or(Vec2::ZERO, |collider| {
+            collider.offset + Vec2::new(collider.width as f32, collider.height as f32) / 2.0
+        })
+}
+
+/// Waypoints through the centers of the cells between `from` and `goal`, empty without a path.
+fn plan_path(nav_grid: &NavGrid, from: Vec2, goal: Vec2) -> Vec<Vec2> {
+    let (Some(start), Some(end)) = (
+        nav_grid.cell_at(from.x, from.y),
+        nav_grid.cell_at(goal.x, goal.y),
+    ) else {
+        return Vec::new();
+    };
+    let Some(cells) = nav_grid.find_path(start, end) else {
+        return Vec::new();
+    };
+
+    let mut path: Vec<Vec2> = cells
+        .into_iter()
+        .map(|cell| Vec2::from(nav_grid.cell_center(cell)))
+        .collect();
+    if let Some(last) = path.last_mut() {
+        *last = goal;
+    }
+    path
+}
+
+type LabelKey = (AssetId, String, pixels::Color);
+
+/// Draws sprites and text labels sorted by `SpriteLayer`, with the tilemap around them.
 pub struct RenderSystem {
     context: Rc<RefCell<WindowCanvas>>,
+    instant: Rc<RefCell<Instant>>,
+    label_textures: HashMap<LabelKey, Texture>,
+}
+
+enum Drawable<'a> {
+    Sprite(&'a SpriteComponent),
+    Label(&'a TextLabelComponent),
+}
+
+impl Drawable<'_> {
+    fn layer(&self) -> &SpriteLayer {
+        match self {
+            Drawable::Sprite(sprite) => &sprite.layer,
+            Drawable::Label(label) => &label.layer,
+        }
+    }
 }
 
 impl RenderSystem {
@@ -78,82 +256,267 @@ impl RenderSystem {
         _: &mut CommandBuffer,
         _: EventEmitter,
     ) {
-        let asset_store_r = query.resources.get::<AssetStore>().borrow();
-        let asset_store = asset_store_r.get::<AssetStore>();
+        let asset_store_r = query.resources.get::<SharedAssetStore>().borrow();
+        let asset_store = asset_store_r.get::<SharedAssetStore>().borrow();
 
         let camera_r = query.resources.get::<Camera>().borrow();
         let camera = camera_r.get::<Camera>();
 
+        let tilemap_r = query.resources.get::<Tilemap>().borrow();
+        let tilemap = tilemap_r.get::<Tilemap>();
+
+        let mut logger_r = query.resources.get::<Logger>().borrow_mut();
+        let logger = logger_r.get_mut::<Logger>();
+
         let transforms = query.components().get::<TransformComponent>();
         let sprites = query.components().get::<SpriteComponent>();
+        let labels = query.components().get::<TextLabelComponent>();
         let mut canvas = data.context.borrow_mut();
+        let elapsed_ms = data.instant.borrow().elapsed().whole_milliseconds().max(0) as u64;
 
-        let (mut ui, mut other): (Vec<_>, Vec<_>) = entities
+        let mut drawables: Vec<_> = entities
             .iter()
             .map(|entity| {
                 (
                     transforms.get(entity.0).unwrap(),
-                    sprites.get(entity.0).unwrap(),
+                    Drawable::Sprite(sprites.get(entity.0).unwrap()),
                 )
             })
-            .partition(|(_, sprite)| matches!(sprite.layer, SpriteLayer::Ui(_)));
+            .collect();
+        // Labels don't need a sprite, so they are found by their component instead of `entities`
+        drawables.extend(labels.iter().enumerate().filter_map(|(id, label)| {
+            let label = label.as_ref().filter(|label| !label.text.is_empty())?;
+            Some((transforms.get(id)?, Drawable::Label(label)))
+        }));
 
-        other.sort_by(|a, b| a.1.layer.cmp(&b.1.layer));
-        ui.sort_by(|a, b| a.1.layer.cmp(&b.1.layer));
+        let (mut ui, mut other): (Vec<_>, Vec<_>) = drawables
+            .into_iter()
+            .partition(|(_, drawable)| matches!(drawable.layer(), SpriteLayer::Ui(_)));
 
-        for (transform, sprite) in other {
-            let texture = asset_store.get_texture(&sprite.asset_id);
-            let src_rect = sprite.src;
+        other.sort_by(|a, b| a.1.layer().cmp(b.1.layer()));
+        ui.sort_by(|a, b| a.1.layer().cmp(b.1.layer()));
 
-            let dst = Rect::new(
-                transform.position.x as i32 - camera.rect.x,
-                transform.position.y as i32 - camera.rect.y,
-                sprite.width * transform.scale.x as u32,
-                sprite.height * transform.scale.y as u32,
-            );
+        let mut used_labels = HashSet::new();
+        let mut draw = |canvas: &mut WindowCanvas, (transform, drawable), offset| {
+            draw_drawable(
+                canvas,
+                &asset_store,
+                &mut data.label_textures,
+                &mut used_labels,
+                logger,
+                transform,
+                drawable,
+                offset,
+            )
+        };
 
-            canvas
-                .copy_ex(
-                    &texture,
-                    Some(src_rect),
-                    Some(dst),
-                    transform.rotation as f64,
-                    None,
-                    false,
-                    false,
-                )
-                .unwrap();
+        draw_tile_layers(&mut canvas, &asset_store, camera, tilemap, elapsed_ms, |kind| {
+            kind != LayerKind::Overhang
+        });
+
+        for item in other {
+            draw(&mut canvas, item, (camera.rect.x, camera.rect.y));
         }
 
-        for (transform, sprite) in ui {
-            let texture = asset_store.get_texture(&sprite.asset_id);
-            let src_rect = sprite.src;
+        draw_tile_layers(&mut canvas, &asset_store, camera, tilemap, elapsed_ms, |kind| {
+            kind == LayerKind::Overhang
+        });
 
-            let dst = Rect::new(
-                transform.position.x as i32,
-                transform.position.y as i32,
-                sprite.width * transform.scale.x as u32,
-                sprite.height * transform.scale.y as u32,
-            );
+        for item in ui {
+            draw(&mut canvas, item, (0, 0));
+        }
 
-            canvas
-                .copy_ex(
-                    &texture,
-                    Some(src_rect),
-                    Some(dst),
-                    transform.rotation as f64,
-                    None,
-                    false,
-                    false,
-                )
-                .unwrap();
+        let unused: Vec<LabelKey> = data
+            .label_textures
+            .keys()
+            .filter(|key| !used_labels.contains(*key))
+            .cloned()
+            .collect();
+        for key in unused {
+            let texture = data.label_textures.remove(&key).unwrap();
+            // The canvas that created it is still alive, so destroying it here is sound
+            unsafe { texture.destroy() };
         }
     }
 }
+
+/// Draws a sprite or label at its transform, shifted by `offset` (the camera for world space).
+#[allow(clippy::too_many_arguments)]
+fn draw_drawable(
+    canvas: &mut WindowCanvas,
+    asset_store: &AssetStore,
+    label_textures: &mut HashMap<LabelKey, Texture>,
+    used_labels: &mut HashSet<LabelKey>,
+    logger: &mut Logger,
+    transform: &TransformComponent,
+    drawable: Drawable,
+    (offset_x, offset_y): (i32, i32),
+) {
+    let (texture, src, width, height) = match drawable {
+        Drawable::Sprite(sprite) => {
+            let (texture, src) = asset_store.get_texture_or_missing(sprite.texture, sprite.src);
+            (texture, Some(src), sprite.width, sprite.height)
+        }
+        Drawable::Label(label) => {
+            let key = (label.font_id.clone(), label.text.clone(), label.color);
+            if !label_textures.contains_key(&key) {
+                match render_label(canvas, asset_store, label) {
+                    Ok(texture) => {
+                        label_textures.insert(key.clone(), texture);
+                    }
+                    Err(err) => {
+                        logger.error(&format!("Failed to render label '{}': {err}", label.text));
+                        return;
+                    }
+                }
+            }
+            let texture = &label_textures[&key];
+            used_labels.insert(key);
+            let query = texture.query();
+            (texture, None, query.width, query.height)
+        }
+    };
+
+    let dst = Rect::new(
+        transform.position.x as i32 - offset_x,
+        transform.position.y as i32 - offset_y,
+        width * transform.scale.x as u32,
+        height * transform.scale.y as u32,
+    );
+
+    canvas
+        .copy_ex(
+            texture,
+            src,
+            Some(dst),
+            transform.rotation as f64,
+            None,
+            false,
+            false,
+        )
+        .unwrap();
+}
+
+fn render_label(
+    canvas: &WindowCanvas,
+    asset_store: &AssetStore,
+    label: &TextLabelComponent,
+) -> Result<Texture, String> {
+    let font = asset_store
+        .get_font(&label.font_id)
+        .map_err(|err| err.to_string())?;
+    let surface = font
+        .render(&label.text)
+        .blended(label.color)
+        .map_err(|err| err.to_string())?;
+    canvas
+        .texture_creator()
+        .create_texture_from_surface(&surface)
+        .map_err(|err| err.to_string())
+}
+
+/// Draws the tiles of the matching layers that the camera sees, in the map's layer order.
+///
+/// Animated tiles show the frame for `elapsed_ms`, so all cells of a tile animate in step.
+fn draw_tile_layers(
+    canvas: &mut WindowCanvas,
+    asset_store: &AssetStore,
+    camera: &Camera,
+    tilemap: &Tilemap,
+    elapsed_ms: u64,
+    draw_layer: impl Fn(LayerKind) -> bool,
+) {
+    let map = tilemap.map.borrow();
+    let tile_size = map.scaled_tile_size();
+    let (columns, rows) = map.cells_in_view(
+        camera.rect.x,
+        camera.rect.y,
+        camera.rect.width(),
+        camera.rect.height(),
+    );
+    // Edges are rounded per cell so fractional scales don't leave gaps between tiles
+    let edge = |cell: u32| (cell as f32 * tile_size) as i32;
+
+    for layer in map.layers.iter().filter(|layer| draw_layer(layer.kind)) {
+        for row in rows.clone() {
+            for column in columns.clone() {
+                let tile = layer.tiles[(row * map.tiles_per_row + column) as usize];
+                if tile == EMPTY_TILE {
+                    continue;
+                }
+                let tile = map.tile_frame(tile, elapsed_ms);
+
+                let src = Rect::new(
+                    (tile % map.tiles_per_file_row * map.tile_size) as i32,
+                    (tile / map.tiles_per_file_row * map.tile_size) as i32,
+                    map.tile_size,
+                    map.tile_size,
+                );
+                let (texture, src) = asset_store.get_texture_or_missing(tilemap.tileset, src);
+                let dst = Rect::new(
+                    edge(column) - camera.rect.x,
+                    edge(row) - camera.rect.y,
+                    (edge(column + 1) - edge(column)) as u32,
+                    (edge(row + 1) - edge(row)) as u32,
+                );
+
+                canvas.copy(texture, Some(src), Some(dst)).unwrap();
+            }
+        }
+    }
+}
+
 impl RenderSystem {
+    pub fn new(context: Rc<RefCell<WindowCanvas>>, instant: Rc<RefCell<Instant>>) -> Self {
+        Self {
+            context,
+            instant,
+            label_textures: HashMap::new(),
+        }
+    }
+}
+
+pub struct LoadingScreenSystem {
+    context: Rc<RefCell<WindowCanvas>>,
+}
+
+impl LoadingScreenSystem {
     pub fn new(context: Rc<RefCell<WindowCanvas>>) -> Self {
         Self { context }
     }
+
+    pub fn action(
+        data: &mut LoadingScreenSystem,
+        query: Query,
+        _: &[Entity],
+        _: &mut CommandBuffer,
+        _: EventEmitter,
+    ) {
+        let progress_r = query.resources.get::<LoadingProgress>().borrow();
+        let progress = progress_r.get::<LoadingProgress>();
+
+        let mut canvas = data.context.borrow_mut();
+
+        let width = game::WINDOW_WIDTH / 2;
+        let height = 24;
+        let bar = Rect::new(
+            (game::WINDOW_WIDTH - width) as i32 / 2,
+            (game::WINDOW_HEIGHT - height) as i32 / 2,
+            width,
+            height,
+        );
+        let filled = Rect::new(
+            bar.x(),
+            bar.y(),
+            (width as f32 * progress.fraction()) as u32,
+            height,
+        );
+
+        canvas.set_draw_color(pixels::Color::RGB(40, 110, 40));
+        canvas.fill_rect(filled).unwrap();
+        canvas.set_draw_color(pixels::Color::GREEN);
+        canvas.draw_rect(bar).unwrap();
+    }
 }
 
 pub struct AnimationSystem {
@@ -175,21 +538,25 @@ impl AnimationSystem {
     ) {
         let mut sprites = query.components().get_mut::<SpriteComponent>();
         let mut animations = query.components().get_mut::<AnimationComponent>();
+        let asset_store_r = query.resources.get::<SharedAssetStore>().borrow();
+        let asset_store = asset_store_r.get::<SharedAssetStore>().borrow();
+
+        let now = data.instant.borrow().elapsed();
 
         for entity in entities {
             let sprite = sprites.get_mut(entity.0).unwrap();
             let animation = animations.get_mut(entity.0).unwrap();
 
-            let current_frame_ms = ((data.instant.borrow().elapsed().whole_milliseconds()
-                - animation.start_time.whole_milliseconds())
-                * animation.frame_rate_speed as i128
-                / 1000)
-                % animation.num_of_frames as i128;
+            let Some(sheet) = asset_store.get_sprite_sheet(sprite.texture) else {
+                continue;
+            };
+            let Some(clip) = sheet.clip(&animation.clip) else {
+                continue;
+            };
 
-            animation.current_frame = current_frame_ms as u32;
-            sprite
-                .src
-                .set_x((animation.current_frame * sprite.width) as i32);
+            let elapsed_ms = (now - animation.start_time).whole_milliseconds().max(0) as u64;
+            animation.current_frame = clip.frame_at(elapsed_ms);
+            sprite.src = sheet.frames[animation.current_frame].rect();
         }
     }
 }
@@ -204,51 +571,121 @@ impl CollisionSystem {
         command_buffer: &mut CommandBuffer,
         emitter: EventEmitter,
     ) {
-        let transforms = query.components().get::<TransformComponent>();
+        let mut transforms = query.components().get_mut::<TransformComponent>();
         let box_colliders = query.components().get::<BoxColliderComponent>();
+        let static_colliders = query.components().get::<StaticColliderComponent>();
+        let rigid_bodies = query.components().get::<RigidBodyComponent>();
+        let airborne = query.components().get::<AirborneComponent>();
+        let projectiles = query.components().get::<ProjectileComponent>();
 
         let mut logger_r = query.resources.get::<Logger>().borrow_mut();
         let mut logger = logger_r.get_mut::<Logger>();
+        let mut spent = HashSet::new();
 
         for (i, entity_a) in entities.iter().enumerate() {
-            let a_transform = transforms.get(entity_a.0).unwrap();
             let a_collider = box_colliders.get(entity_a.0).unwrap();
 
             for entity_b in entities[i + 1..].iter() {
-                let b_transform = transforms.get(entity_b.0).unwrap();
+                let a_transform = *transforms.get(entity_a.0).unwrap();
+                let b_transform = *transforms.get(entity_b.0).unwrap();
                 let b_collider = box_colliders.get(entity_b.0).unwrap();
 
                 let a_offset = a_transform.position + a_collider.offset;
                 let b_offset = b_transform.position + b_collider.offset;
 
-                let collided = check_AABB_collision(
-                    a_offset.x as u32,
-                    a_offset.y as u32,
-                    a_collider.width,
-                    a_collider.height,
-                    b_offset.x as u32,
-                    b_offset.y as u32,
-                    b_collider.width,
-                    b_collider.height,
-                );
+                let a_static = static_colliders.get(entity_a.0);
+                let b_static = static_colliders.get(entity_b.0);
+                let (mover, mover_offset, mover_collider, wall_offset, wall_collider, wall) =
+                    match (a_static, b_static) {
+                        (Some(_), Some(_)) => continue,
+                        (None, Some(wall)) => {
+                            (entity_a, a_offset, a_collider, b_offset, b_collider, wall)
+                        }
+                        (Some(wall), None) => {
+                            (entity_b, b_offset, b_collider, a_offset, a_collider, wall)
+                        }
+                        (None, None) => {
+                            let collided = check_AABB_collision(
+                                a_offset.x as u32,
+                                a_offset.y as u32,
+                                a_collider.width,
+                                a_collider.height,
+                                b_offset.x as u32,
+                                b_offset.y as u32,
+                                b_collider.width,
+                                b_collider.height,
+                            );
+
+                            if collided {
+                                logger.warn(&format!(
+                                    "Entity {} and {} collided",
+                                    entity_a.0, entity_b.0
+                                ));
+
+                                emitter.emit(
+                                    Collision {
+                                        a: entity_a.0,
+                                        b: entity_b.0,
+                                    },
+                                    command_buffer,
+                                    &query,
+                                );
+                            }
+                            continue;
+                        }
+                    };
+
+                if rigid_bodies.get(mover.0).is_none()
+                    || !wall.surface.blocks_movement()
+                    || (airborne.get(mover.0).is_some() && wall.surface != TileSurface::Solid)
+                {
+                    continue;
+                }
+                let Some(overlap) = aabb_overlap(
+                    mover_offset,
+                    mover_collider,
+                    wall_offset,
+                    wall_collider,
+                ) else {
+                    continue;
+                };
 
-                if collided {
-                    logger.warn(&format!(
-                        "Entity {} and {} collided",
-                        entity_a.0, entity_b.0
-                    ));
-
-                    emitter.emit(
-                        Collision {
-                            a: entity_a.0,
-                            b: entity_b.0,
-                        },
-                        command_buffer,
-                        &query,
-                    );
+                if projectiles.get(mover.0).is_some() {
+                    spent.insert(mover.0);
+                } else {
+                    transforms.get_mut(mover.0).unwrap().position += overlap;
                 }
             }
         }
+
+        for projectile in spent {
+            command_buffer.remove_entity(&Entity(projectile));
+        }
+    }
+}
+
+/// Shortest push that moves `a` out of `b`, `None` when they don't overlap.
+fn aabb_overlap(
+    a_position: Vec2,
+    a: &BoxColliderComponent,
+    b_position: Vec2,
+    b: &BoxColliderComponent,
+) -> Option<Vec2> {
+    let a_size = Vec2::new(a.width as f32, a.height as f32);
+    let b_size = Vec2::new(b.width as f32, b.height as f32);
+    let overlap = (a_position + a_size).min(b_position + b_size) - a_position.max(b_position);
+    if overlap.x <= 0.0 || overlap.y <= 0.0 {
+        return None;
+    }
+
+    let a_center = a_position + a_size / 2.0;
+    let b_center = b_position + b_size / 2.0;
+    if overlap.x < overlap.y {
+        let direction = if a_center.x < b_center.x { -1.0 } else { 1.0 };
+        Some(Vec2::new(overlap.x * direction, 0.0))
+    } else {
+        let direction = if a_center.y < b_center.y { -1.0 } else { 1.0 };
+        Some(Vec2::new(0.0, overlap.y * direction))
     }
 }
 
@@ -265,6 +702,162 @@ fn check_AABB_collision(
     a_x < b_x + b_width && a_x + a_width > b_x && a_y < b_y + b_height && a_y + a_height > b_y
 }
 
+/// Size of a bullet sprite and collider, in pixels.
+const BULLET_SIZE: u32 = 4;
+
+/// Spawns bullets from the center of entities with a `ProjectileEmitterComponent`.
+pub struct ProjectileEmitterSystem;
+
+impl ProjectileEmitterSystem {
+    pub fn action(
+        _: &mut ProjectileEmitterSystem,
+        query: Query,
+        entities: &[Entity],
+        command_buffer: &mut CommandBuffer,
+        _: EventEmitter,
+    ) {
+        let transforms = query.components().get::<TransformComponent>();
+        let box_colliders = query.components().get::<BoxColliderComponent>();
+        let mut emitters = query.components().get_mut::<ProjectileEmitterComponent>();
+        let delta_time = query
+            .resources
+            .get::<DeltaTime>()
+            .borrow()
+            .get::<DeltaTime>()
+            .0
+            .as_seconds_f32();
+
+        for entity in entities {
+            let transform = transforms.get(entity.0).unwrap();
+            let emitter = emitters.get_mut(entity.0).unwrap();
+
+            emitter.cooldown = (emitter.cooldown - delta_time).max(0.0);
+            let fire = emitter.automatic || emitter.triggered;
+            let direction = emitter.direction.normalize_or_zero();
+            if !fire || emitter.cooldown > 0.0 || direction == Vec2::ZERO {
+                continue;
+            }
+            emitter.triggered = false;
+            emitter.cooldown = 1.0 / emitter.fire_rate.max(f32::EPSILON);
+
+            let center = entity_center(transform, box_colliders.get(entity.0));
+            command_buffer
+                .create_entity()
+                .with_component(
+                    TransformComponentBuilder::default()
+                        .position(center - Vec2::splat(BULLET_SIZE as f32 / 2.0))
+                        .build()
+                        .unwrap(),
+                )
+                .with_component(RigidBodyComponent {
+                    velocity: direction * emitter.speed,
+                })
+                .with_component(SpriteComponent::enemy(
+                    BULLET_SIZE,
+                    BULLET_SIZE,
+                    emitter.sprite,
+                ))
+                .with_component(
+                    BoxColliderComponentBuilder::default()
+                        .width(BULLET_SIZE)
+                        .height(BULLET_SIZE)
+                        .build()
+                        .unwrap(),
+                )
+                .with_component(
+                    DamageComponentBuilder::default()
+                        .amount(emitter.damage)
+                        .faction(emitter.faction)
+                        .destroy_on_hit(true)
+                        .build()
+                        .unwrap(),
+                )
+                .with_component(ProjectileComponent {
+                    lifetime: emitter.lifetime,
+                })
+                .with_component(AirborneComponent)
+                .finish_entity();
+        }
+    }
+}
+
+/// Removes bullets whose lifetime ran out.
+pub struct ProjectileSystem;
+
+impl ProjectileSystem {
+    pub fn action(
+        _: &mut ProjectileSystem,
+        query: Query,
+        entities: &[Entity],
+        command_buffer: &mut CommandBuffer,
+        _: EventEmitter,
+    ) {
+        let mut projectiles = query.components().get_mut::<ProjectileComponent>();
+        let delta_time = query
+            .resources
+            .get::<DeltaTime>()
+            .borrow()
+            .get::<DeltaTime>()
+            .0
+            .as_seconds_f32();
+
+        for entity in entities {
+            let projectile = projectiles.get_mut(entity.0).unwrap();
+            projectile.lifetime -= delta_time;
+            if projectile.lifetime <= 0.0 {
+                command_buffer.remove_entity(entity);
+            }
+        }
+    }
+}
+
+/// Counts down invulnerability after hits and removes entities that ran out of hit points,
+/// emitting `EntityDied` for them first.
+pub struct HealthSystem;
+
+impl HealthSystem {
+    pub fn action(
+        _: &mut HealthSystem,
+        query: Query,
+        entities: &[Entity],
+        command_buffer: &mut CommandBuffer,
+        emitter: EventEmitter,
+    ) {
+        let delta_time = query
+            .resources
+            .get::<DeltaTime>()
+            .borrow()
+            .get::<DeltaTime>()
+            .0
+            .as_seconds_f32();
+
+        let mut died = Vec::new();
+        {
+            let mut healths = query.components().get_mut::<HealthComponent>();
+            for entity in entities {
+                let health = healths.get_mut(entity.0).unwrap();
+                health.invulnerable_for = (health.invulnerable_for - delta_time).max(0.0);
+                if health.is_dead() {
+                    died.push((*entity, health.faction));
+                }
+            }
+        }
+
+        // Subscribers may read the health of the dead entity, so it's no longer borrowed here
+        for (entity, faction) in died {
+            emitter.emit(
+                EntityDied {
+                    entity: entity.0,
+                    faction,
+                },
+                command_buffer,
+                &query,
+            );
+            command_buffer.remove_entity(&entity);
+        }
+    }
+}
+
 pub struct DebugSystem {
     context: Rc<RefCell<WindowCanvas>>,
 }
@@ -288,6 +881,7 @@ impl DebugSystem {
 
         let transforms = query.components().get::<TransformComponent>();
         let colliders = query.components().get::<BoxColliderComponent>();
+        let healths = query.components().get::<HealthComponent>();
         let mut canvas = data.context.borrow_mut();
 
         for entity in entities {
@@ -305,6 +899,86 @@ impl DebugSystem {
 
             canvas.set_draw_color(pixels::Color::GREEN);
             canvas.draw_rect(collider_rect).unwrap();
+
+            // Hit points left as a bar above the collider, red while invulnerable
+            if let Some(health) = healths.get(entity.0) {
+                let width = collider_rect.width() * health.hit_points / health.max_hit_points.max(1);
+                canvas.set_draw_color(if health.invulnerable_for > 0.0 {
+                    pixels::Color::RED
+                } else {
+                    pixels::Color::GREEN
+                });
+                canvas
+                    .fill_rect(Rect::new(
+                        collider_rect.x(),
+                        collider_rect.y() - 6,
+                        width.max(1),
+                        3,
+                    ))
+                    .unwrap();
+            }
+        }
+    }
+}
+
+/// Runs the tilemap editor over the level while it is toggled on.
+pub struct TilemapEditorSystem {
+    editor: Rc<RefCell<TilemapEditor>>,
+    context: Rc<RefCell<WindowCanvas>>,
+    reloaded_map: ReloadedMap,
+}
+
+impl TilemapEditorSystem {
+    pub fn new(
+        editor: Rc<RefCell<TilemapEditor>>,
+        context: Rc<RefCell<WindowCanvas>>,
+        reloaded_map: ReloadedMap,
+    ) -> Self {
+        Self {
+            editor,
+            context,
+            reloaded_map,
+        }
+    }
+
+    pub fn action(
+        data: &mut TilemapEditorSystem,
+        query: Query,
+        entities: &[Entity],
+        cmd_buffer: &mut CommandBuffer,
+        _: EventEmitter,
+    ) {
+        let camera_r = query.resources.get::<Camera>().borrow();
+        let camera = camera_r.get::<Camera>();
+
+        let asset_store_r = query.resources.get::<SharedAssetStore>().borrow();
+        let asset_store = asset_store_r.get::<SharedAssetStore>().borrow();
+
+        let delta_time_r = query.resources.get::<DeltaTime>().borrow();
+        let delta_time = delta_time_r.get::<DeltaTime>();
+
+        let tilemap_r = query.resources.get::<Tilemap>().borrow();
+        let tilemap = tilemap_r.get::<Tilemap>();
+
+        let mut logger_r = query.resources.get::<Logger>().borrow_mut();
+        let logger = logger_r.get_mut::<Logger>();
+
+        let mut canvas = data.context.borrow_mut();
+        let edited = data.editor.borrow_mut().frame(
+            &mut canvas,
+            delta_time.0,
+            camera,
+            &asset_store,
+            tilemap,
+            logger,
+        );
+
+        // Colliders and path costs are rebuilt like for a reloaded map, with the next update
+        if edited {
+            for entity in entities {
+                cmd_buffer.remove_entity(entity);
+            }
+            *data.reloaded_map.borrow_mut() = Some((tilemap.map.clone(), tilemap.tileset));
         }
     }
 }
@@ -356,9 +1030,172 @@ impl CameraMovementSystem {
     }
 }
 
-pub fn collision_event_handler(event: &Collision, _: &Query, cmd_buffer: &mut CommandBuffer) {
-    cmd_buffer.remove_entity(&Entity(event.a));
-    cmd_buffer.remove_entity(&Entity(event.b));
+#[derive(Default)]
+pub struct AudioSystem {
+    playing: HashMap<usize, (Channel, Rc<Chunk>)>,
+}
+
+impl AudioSystem {
+    pub fn new() -> Self {
+        Self {
+            playing: HashMap::new(),
+        }
+    }
+
+    pub fn action(
+        data: &mut AudioSystem,
+        query: Query,
+        entities: &[Entity],
+        _: &mut CommandBuffer,
+        _: EventEmitter,
+    ) {
+        let asset_store_r = query.resources.get::<SharedAssetStore>().borrow();
+        let asset_store = asset_store_r.get::<SharedAssetStore>().borrow();
+
+        let sources = query.components().get::<AudioSourceComponent>();
+
+        // Silence sources whose entities were removed since the last update
+        let alive: HashSet<usize> = entities.iter().map(|entity| entity.0).collect();
+        data.playing.retain(|id, (channel, chunk)| {
+            let still_ours = channel.is_playing()
+                && channel
+                    .get_chunk()
+                    .is_some_and(|playing| playing.raw == chunk.raw);
+            if !alive.contains(id) && still_ours {
+                channel.halt();
+            }
+            alive.contains(id)
+        });
+
+        for entity in entities {
+            if data.playing.contains_key(&entity.0) {
+                continue;
+            }
+
+            let source = sources.get(entity.0).unwrap();
+            let Ok(sound) = asset_store.get_sound(&source.sound_id) else {
+                continue;
+            };
+
+            let loops = if source.looping { -1 } else { 0 };
+            if let Ok(channel) = play_chunk(&sound, loops) {
+                channel.set_volume(source.volume);
+                data.playing.insert(entity.0, (channel, sound));
+            }
+        }
+    }
+}
+
+/// Filled by `HotReloadSystem` with the reloaded level map and its tileset, the game respawns the tile colliders.
+pub type ReloadedMap = Rc<RefCell<Option<(SharedMap, TextureHandle)>>>;
+
+pub struct HotReloadSystem {
+    watcher: AssetWatcher,
+    map_id: AssetId,
+    reloaded_map: ReloadedMap,
+}
+
+impl HotReloadSystem {
+    pub fn new(map_id: AssetId, reloaded_map: ReloadedMap) -> Self {
+        Self {
+            watcher: AssetWatcher::new(&WATCHED_DIRECTORIES, POLL_INTERVAL),
+            map_id,
+            reloaded_map,
+        }
+    }
+
+    pub fn action(
+        data: &mut HotReloadSystem,
+        query: Query,
+        entities: &[Entity],
+        cmd_buffer: &mut CommandBuffer,
+        _: EventEmitter,
+    ) {
+        let changed = data.watcher.poll();
+        if changed.is_empty() {
+            return;
+        }
+
+        let asset_store_r = query.resources.get::<SharedAssetStore>().borrow();
+        let mut asset_store = asset_store_r.get::<SharedAssetStore>().borrow_mut();
+
+        let mut logger_r = query.resources.get::<Logger>().borrow_mut();
+        let logger = logger_r.get_mut::<Logger>();
+
+        for path in changed {
+            let reloaded = match asset_store.reload_file(&path) {
+                Ok(reloaded) => reloaded,
+                Err(err) => {
+                    logger.error(&format!("Failed to reload {}: {err}", path.display()));
+                    continue;
+                }
+            };
+
+            for asset in reloaded {
+                logger.info(&format!("Reloaded {asset:?} from {}", path.display()));
+
+                if asset != AssetKey::Tilemap(data.map_id.clone()) {
+                    continue;
+                }
+
+                // The game respawns tile colliders from the new map, the old ones go away with the next update
+                for entity in entities {
+                    cmd_buffer.remove_entity(entity);
+                }
+                *data.reloaded_map.borrow_mut() =
+                    asset_store.get_tilemap(&data.map_id).ok().map(|map| {
+                        let tileset = asset_store.texture_handle_or_missing(&map.borrow().tileset);
+                        (map, tileset)
+                    });
+            }
+        }
+    }
+}
+
+/// Hurts each side of a collision with the damage of the other, when they fight on different sides.
+pub fn collision_event_handler(event: &Collision, query: &Query, cmd_buffer: &mut CommandBuffer) {
+    let damages = query.components().get::<DamageComponent>();
+    let mut healths = query.components().get_mut::<HealthComponent>();
+
+    for (attacker, target) in [(event.a, event.b), (event.b, event.a)] {
+        let (Some(damage), Some(health)) = (damages.get(attacker), healths.get_mut(target)) else {
+            continue;
+        };
+        if damage.faction == health.faction {
+            continue;
+        }
+
+        health.take_damage(damage.amount);
+        // A bullet is spent even when the target is still invulnerable from the last hit.
+        if damage.destroy_on_hit {
+            cmd_buffer.remove_entity(&Entity(attacker));
+        }
+    }
+}
+
+pub fn entity_died_handler(event: &EntityDied, query: &Query, _: &mut CommandBuffer) {
+    play_one_shot(query, "explosion");
+
+    let mut logger_r = query.resources.get::<Logger>().borrow_mut();
+    let logger = logger_r.get_mut::<Logger>();
+    logger.info(&format!(
+        "Entity {} of faction {:?} died",
+        event.entity, event.faction
+    ));
+}
+
+pub fn level_unloaded_handler(event: &LevelUnloaded, query: &Query, cmd_buffer: &mut CommandBuffer) {
+    // Everything a level spawns is placed in the world, tile colliders and HUD elements included
+    let transforms = query.components().get::<TransformComponent>();
+    let mut removed = 0;
+    for (id, _) in transforms.iter().enumerate().filter(|(_, transform)| transform.is_some()) {
+        cmd_buffer.remove_entity(&Entity(id));
+        removed += 1;
+    }
+
+    let mut logger_r = query.resources.get::<Logger>().borrow_mut();
+    let logger = logger_r.get_mut::<Logger>();
+    logger.info(&format!("Removed {removed} entities of level {}", event.level));
 }
 
 pub fn key_pressed_hanlder(event: &KeyPressed, query: &Query, cmd_buffer: &mut CommandBuffer) {
@@ -366,36 +1203,42 @@ pub fn key_pressed_hanlder(event: &KeyPressed, query: &Query, cmd_buffer: &mut C
     let mut logger = logger_r.get_mut::<Logger>();
 
     let keyboard_components = query.components().get::<KeyboardControlledComponent>();
-    let mut sprites = query.components().get_mut::<SpriteComponent>();
+    let mut animations = query.components().get_mut::<AnimationComponent>();
     let mut rigid_bodies = query.components().get_mut::<RigidBodyComponent>();
+    let mut emitters = query.components().get_mut::<ProjectileEmitterComponent>();
 
     for (id, keyboard_comp) in keyboard_components
         .iter()
         .enumerate()
         .filter(|(_, comp)| comp.is_some())
     {
-        let mut sprite = sprites.get_mut(id).unwrap();
         let mut rigid_body = rigid_bodies.get_mut(id).unwrap();
         let keyboard_comp = keyboard_comp.as_ref().unwrap();
 
-        match event.key {
-            Keycode::Up => {
-                rigid_body.velocity = keyboard_comp.up_velocity;
-                sprite.src.y = sprite.height as i32 * 3;
-            }
-            Keycode::Right => {
-                rigid_body.velocity = keyboard_comp.right_velocity;
-                sprite.src.y = 0;
-            }
-            Keycode::Down => {
-                rigid_body.velocity = keyboard_comp.down_velocity;
-                sprite.src.y = sprite.height as i32;
+        if event.key == Keycode::Space {
+            if let Some(emitter) = emitters.get_mut(id) {
+                emitter.triggered = true;
             }
-            Keycode::Left => {
-                rigid_body.velocity = keyboard_comp.left_velocity;
-                sprite.src.y = sprite.height as i32 * 2;
+            continue;
+        }
+
+        let (velocity, clip) = match event.key {
+            Keycode::Up => (keyboard_comp.up_velocity, "fly_up"),
+            Keycode::Right => (keyboard_comp.right_velocity, "fly_right"),
+            Keycode::Down => (keyboard_comp.down_velocity, "fly_down"),
+            Keycode::Left => (keyboard_comp.left_velocity, "fly_left"),
+            _ => continue,
+        };
+
+        rigid_body.velocity = velocity;
+        if let Some(animation) = animations.get_mut(id) {
+            animation.play(clip);
+        }
+        // The chopper fires where it's heading
+        if let Some(emitter) = emitters.get_mut(id) {
+            if velocity != Vec2::ZERO {
+                emitter.direction = velocity;
             }
-            _ => {}
         }
     }
 
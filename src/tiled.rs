@@ -0,0 +1,518 @@
+//! Imports maps exported from the Tiled editor as JSON (`.tmj` or `.json`).
+
+use serde::{de::IgnoredAny, Deserialize};
+
+use std::collections::HashMap;
+
+use crate::map::{
+    LayerKind, Map, MapBuilder, SpawnPoint, TileAnimation, TileLayer, TileSurface, EMPTY_TILE,
+};
+
+/// Tiled keeps flip and rotation flags in the top bits of each global tile id.
+const GID_FLAGS: u32 = 0xF000_0000;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Orientation {
+    Orthogonal,
+    Isometric,
+    Staggered,
+    Hexagonal,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledMap {
+    orientation: Orientation,
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    infinite: bool,
+    layers: Vec<Layer>,
+    tilesets: Vec<TilesetRef>,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+/// Custom property, on the map `tile_scale` sets how much tiles are scaled up on screen
+/// and on tile layers `layer` picks ground, decoration or overhang.
+#[derive(Debug, Clone, Deserialize)]
+struct Property {
+    name: String,
+    value: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Layer {
+    #[serde(rename = "tilelayer")]
+    Tiles {
+        name: String,
+        #[serde(default = "default_visible")]
+        visible: bool,
+        #[serde(default)]
+        properties: Vec<Property>,
+        #[serde(default)]
+        encoding: Option<String>,
+        data: LayerData,
+    },
+    ObjectGroup {
+        #[serde(default = "default_visible")]
+        visible: bool,
+        objects: Vec<Object>,
+    },
+    Group {
+        #[serde(default = "default_visible")]
+        visible: bool,
+        layers: Vec<Layer>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// CSV layers hold an array of global tile ids, base64 ones a string.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum LayerData {
+    Tiles(Vec<u32>),
+    Encoded(IgnoredAny),
+}
+
+/// Spawn point, Tiled 1.9 exported the object type as `class`.
+#[derive(Debug, Deserialize)]
+struct Object {
+    name: String,
+    #[serde(rename = "type", alias = "class", default)]
+    kind: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+impl Object {
+    fn spawn_point(&self) -> SpawnPoint {
+        let properties = self
+            .properties
+            .iter()
+            .map(|property| {
+                let value = match property.value.as_str() {
+                    Some(value) => value.to_owned(),
+                    None => property.value.to_string(),
+                };
+                (property.name.clone(), value)
+            })
+            .collect();
+        SpawnPoint {
+            name: self.name.clone(),
+            kind: self.kind.clone(),
+            x: self.x,
+            y: self.y,
+            properties,
+        }
+    }
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+/// Either an embedded tileset or a reference to an external `.tsj` file.
+#[derive(Debug, Deserialize)]
+struct TilesetRef {
+    firstgid: u32,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(flatten)]
+    tileset: Option<Tileset>,
+}
+
+/// The tileset name doubles as the asset id of its texture.
+#[derive(Debug, Clone, Deserialize)]
+struct Tileset {
+    name: String,
+    columns: u32,
+    tilecount: u32,
+    #[serde(default)]
+    tiles: Vec<TileDefinition>,
+}
+
+/// Per-tile data of a tileset, the boolean `solid`, `water` and `slow` properties set its surface.
+#[derive(Debug, Clone, Deserialize)]
+struct TileDefinition {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<Property>,
+    #[serde(default)]
+    animation: Vec<AnimationFrame>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AnimationFrame {
+    tileid: u32,
+    duration: u32,
+}
+
+impl Tileset {
+    fn surfaces(&self) -> HashMap<u32, TileSurface> {
+        self.tiles
+            .iter()
+            .filter_map(|tile| {
+                let is_set = |name: &str| {
+                    tile.properties
+                        .iter()
+                        .any(|property| property.name == name && property.value == true)
+                };
+                let surface = if is_set("solid") {
+                    TileSurface::Solid
+                } else if is_set("water") {
+                    TileSurface::Water
+                } else if is_set("slow") {
+                    TileSurface::Slow
+                } else {
+                    return None;
+                };
+                Some((tile.id, surface))
+            })
+            .collect()
+    }
+
+    fn animations(&self) -> Result<HashMap<u32, TileAnimation>, String> {
+        let mut animations = HashMap::new();
+        for tile in self.tiles.iter().filter(|tile| !tile.animation.is_empty()) {
+            if let Some(frame) = tile
+                .animation
+                .iter()
+                .find(|frame| frame.tileid >= self.tilecount || frame.duration == 0)
+            {
+                return Err(format!(
+                    "tile {} has an invalid animation frame {} of {}ms",
+                    tile.id, frame.tileid, frame.duration
+                ));
+            }
+            let animation = TileAnimation {
+                frames: tile.animation.iter().map(|frame| frame.tileid).collect(),
+                durations: tile.animation.iter().map(|frame| frame.duration).collect(),
+            };
+            animations.insert(tile.id, animation);
+        }
+        Ok(animations)
+    }
+}
+
+/// Builds a `Map` from a Tiled JSON export, `read_tileset` loads external tilesets by their `source`.
+///
+/// Every visible tile layer becomes a layer of the map. Its kind comes from a `layer` property
+/// or the layer name, otherwise the first layer is the ground and the ones above decoration.
+/// Objects of visible object layers become spawn points, their type is the spawn point kind.
+pub fn parse_tiled_map(
+    data: &str,
+    mut read_tileset: impl FnMut(&str) -> Result<String, String>,
+) -> Result<Map, String> {
+    let map: TiledMap = serde_json::from_str(data).map_err(|err| err.to_string())?;
+
+    if !matches!(map.orientation, Orientation::Orthogonal) {
+        return Err(format!(
+            "{:?} maps are not supported, only orthogonal ones",
+            map.orientation
+        ));
+    }
+    if map.infinite {
+        return Err("infinite maps are not supported".to_owned());
+    }
+    if map.tilewidth != map.tileheight {
+        return Err(format!(
+            "tiles must be square, got {}x{}",
+            map.tilewidth, map.tileheight
+        ));
+    }
+
+    let mut tilesets = map.tilesets.iter().collect::<Vec<_>>();
+    tilesets.sort_by_key(|tileset| tileset.firstgid);
+    let (tileset, next_tileset) = match tilesets.as_slice() {
+        [] => return Err("the map has no tileset".to_owned()),
+        [tileset, rest @ ..] => (*tileset, rest.first().map(|next| next.firstgid)),
+    };
+
+    let (firstgid, tileset) = match (&tileset.source, &tileset.tileset) {
+        (_, Some(embedded)) => (tileset.firstgid, embedded.clone()),
+        (Some(source), None) => (
+            tileset.firstgid,
+            serde_json::from_str(&read_tileset(source)?)
+                .map_err(|err| format!("tileset '{source}': {err}"))?,
+        ),
+        (None, None) => return Err("the tileset has no name or columns".to_owned()),
+    };
+    if tileset.columns == 0 {
+        return Err(format!(
+            "tileset '{}' is an image collection, only tilesets from a single image are supported",
+            tileset.name
+        ));
+    }
+
+    let tile_scale = match map
+        .properties
+        .iter()
+        .find(|property| property.name == "tile_scale")
+    {
+        Some(property) => property
+            .value
+            .as_f64()
+            .ok_or_else(|| format!("tile_scale must be a number, got {}", property.value))?
+            as f32,
+        None => 1.0,
+    };
+
+    let cells = (map.width * map.height) as usize;
+    let mut layers = Vec::new();
+    let mut spawns = Vec::new();
+    collect_layers(&map.layers, cells, &mut layers, &mut spawns)?;
+    if layers.is_empty() {
+        return Err("the map has no visible tile layers".to_owned());
+    }
+
+    for tile in layers
+        .iter_mut()
+        .flat_map(|layer| layer.tiles.iter_mut())
+        .filter(|tile| **tile != EMPTY_TILE)
+    {
+        if next_tileset.is_some_and(|firstgid| *tile >= firstgid) {
+            return Err(format!(
+                "tile {tile} comes from a second tileset, only one is supported"
+            ));
+        }
+        let Some(index) = tile
+            .checked_sub(firstgid)
+            .filter(|index| *index < tileset.tilecount)
+        else {
+            return Err(format!(
+                "tile {tile} is outside the tileset's {} tiles",
+                tileset.tilecount
+            ));
+        };
+        *tile = index;
+    }
+
+    MapBuilder::default()
+        .tile_surfaces(tileset.surfaces())
+        .tile_animations(tileset.animations()?)
+        .tileset(tileset.name)
+        .tile_size(map.tilewidth)
+        .tiles_per_row(map.width)
+        .tiles_per_file_row(tileset.columns)
+        .tileset_rows(tileset.tilecount.div_ceil(tileset.columns))
+        .layers(layers)
+        .spawns(spawns)
+        .tile_scale(tile_scale)
+        .build()
+        .map_err(|err| err.to_string())
+}
+
+fn collect_layers(
+    layers: &[Layer],
+    cells: usize,
+    collected: &mut Vec<TileLayer>,
+    spawns: &mut Vec<SpawnPoint>,
+) -> Result<(), String> {
+    for layer in layers {
+        match layer {
+            Layer::Tiles {
+                name,
+                visible,
+                properties,
+                encoding,
+                data,
+            } => {
+                if !visible {
+                    continue;
+                }
+                let data = match data {
+                    LayerData::Tiles(data) => data,
+                    LayerData::Encoded(_) => {
+                        return Err(format!(
+                            "layer '{name}' is {} encoded, export it as CSV",
+                            encoding.as_deref().unwrap_or("base64")
+                        ))
+                    }
+                };
+                if data.len() != cells {
+                    return Err(format!(
+                        "layer '{name}' has {} tiles, expected {cells}",
+                        data.len()
+                    ));
+                }
+
+                let kind = match properties.iter().find(|property| property.name == "layer") {
+                    Some(property) => property
+                        .value
+                        .as_str()
+                        .and_then(LayerKind::from_name)
+                        .ok_or_else(|| {
+                            format!("layer '{name}' has an unknown kind {}", property.value)
+                        })?,
+                    None => LayerKind::from_name(&name.to_lowercase()).unwrap_or(
+                        if collected.is_empty() {
+                            LayerKind::Ground
+                        } else {
+                            LayerKind::Decoration
+                        },
+                    ),
+                };
+
+                let tiles = data
+                    .iter()
+                    .map(|gid| match gid & !GID_FLAGS {
+                        0 => EMPTY_TILE,
+                        gid => gid,
+                    })
+                    .collect();
+                collected.push(TileLayer { kind, tiles });
+            }
+            Layer::ObjectGroup { visible, objects } => {
+                if *visible {
+                    spawns.extend(objects.iter().map(Object::spawn_point));
+                }
+            }
+            Layer::Group { visible, layers } => {
+                if *visible {
+                    collect_layers(layers, cells, collected, spawns)?;
+                }
+            }
+            Layer::Other => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_tiled_map;
+    use crate::map::{LayerKind, TileSurface, EMPTY_TILE};
+
+    const MAP: &str = r#"{
+        "orientation": "orthogonal",
+        "width": 3,
+        "height": 2,
+        "tilewidth": 16,
+        "tileheight": 16,
+        "infinite": false,
+        "properties": [{ "name": "tile_scale", "type": "float", "value": 2.0 }],
+        "tilesets": [{ "firstgid": 1, "source": "jungle.tsj" }],
+        "layers": [
+            { "type": "tilelayer", "name": "ground", "data": [1, 2, 3, 4, 5, 6] },
+            { "type": "objectgroup", "name": "spawns", "objects": [
+                { "name": "tank", "type": "enemy", "x": 392, "y": 72.5, "point": true,
+                  "properties": [{ "name": "velocity_x", "type": "float", "value": 25 },
+                                 { "name": "sprite", "type": "string", "value": "tank" }] }
+            ] },
+            { "type": "group", "layers": [
+                { "type": "tilelayer", "name": "trees", "data": [0, 0, 2147483658, 0, 0, 0],
+                  "properties": [{ "name": "layer", "type": "string", "value": "overhang" }] }
+            ] },
+            { "type": "tilelayer", "name": "hidden", "visible": false, "data": [9, 9, 9, 9, 9, 9] }
+        ]
+    }"#;
+
+    #[test]
+    fn imports_layers_and_resolves_external_tileset() {
+        let map = parse_tiled_map(MAP, |source| {
+            assert_eq!(source, "jungle.tsj");
+            Ok(r#"{
+                "name": "jungle", "columns": 10, "tilecount": 30,
+                "tiles": [{ "id": 21, "properties": [{ "name": "water", "type": "bool", "value": true }],
+                            "animation": [{ "tileid": 21, "duration": 300 }, { "tileid": 22, "duration": 200 }] }]
+            }"#
+            .to_owned())
+        })
+        .unwrap();
+
+        assert_eq!(map.tileset, "jungle");
+        assert_eq!(map.tile_surfaces.get(&21), Some(&TileSurface::Water));
+        assert_eq!(map.tile_frame(21, 350), 22);
+        assert_eq!(map.tile_size, 16);
+        assert_eq!(map.tile_scale, 2.0);
+        assert_eq!(map.tiles_per_row, 3);
+        assert_eq!(map.tiles_per_file_row, 10);
+        assert_eq!(map.layers.len(), 2);
+        assert_eq!(map.layers[0].kind, LayerKind::Ground);
+        assert_eq!(map.layers[0].tiles, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(map.layers[1].kind, LayerKind::Overhang);
+        assert_eq!(map.spawns.len(), 1);
+        assert_eq!(map.spawns[0].kind, "enemy");
+        assert_eq!((map.spawns[0].x, map.spawns[0].y), (392.0, 72.5));
+        assert_eq!(map.spawns[0].property("velocity_x", 0.0), Ok(25.0));
+        assert_eq!(
+            map.spawns[0].property("sprite", String::new()),
+            Ok("tank".to_owned())
+        );
+        assert_eq!(
+            map.layers[1].tiles,
+            vec![EMPTY_TILE, EMPTY_TILE, 9, EMPTY_TILE, EMPTY_TILE, EMPTY_TILE]
+        );
+    }
+
+    #[test]
+    fn keeps_empty_cells_empty() {
+        let map = parse_tiled_map(
+            r#"{
+                "orientation": "orthogonal",
+                "width": 2, "height": 1, "tilewidth": 32, "tileheight": 32,
+                "tilesets": [{ "firstgid": 1, "name": "desert", "columns": 4, "tilecount": 8 }],
+                "layers": [{ "type": "tilelayer", "name": "ground", "data": [0, 3] }]
+            }"#,
+            |_| unreachable!(),
+        )
+        .unwrap();
+
+        assert_eq!(map.layers[0].tiles, vec![EMPTY_TILE, 2]);
+    }
+
+    #[test]
+    fn rejects_image_collections_and_tiles_outside_the_tileset() {
+        let map = |tileset: &str, data: &str| {
+            parse_tiled_map(
+                &format!(
+                    r#"{{
+                        "orientation": "orthogonal",
+                        "width": 2, "height": 1, "tilewidth": 32, "tileheight": 32,
+                        "tilesets": [{tileset}],
+                        "layers": [{{ "type": "tilelayer", "name": "ground", "data": {data} }}]
+                    }}"#
+                ),
+                |_| unreachable!(),
+            )
+        };
+
+        assert_eq!(
+            map(
+                r#"{ "firstgid": 1, "name": "props", "columns": 0, "tilecount": 8 }"#,
+                "[1, 2]"
+            )
+            .unwrap_err(),
+            "tileset 'props' is an image collection, only tilesets from a single image are supported"
+        );
+        assert_eq!(
+            map(
+                r#"{ "firstgid": 5, "name": "desert", "columns": 4, "tilecount": 8 }"#,
+                "[5, 2]"
+            )
+            .unwrap_err(),
+            "tile 2 is outside the tileset's 8 tiles"
+        );
+    }
+
+    #[test]
+    fn rejects_base64_layers() {
+        let err = parse_tiled_map(
+            &MAP.replace(
+                r#""name": "ground", "data": [1, 2, 3, 4, 5, 6]"#,
+                r#""name": "ground", "encoding": "base64", "data": "AQAAAA==""#,
+            ),
+            |_| Ok(r#"{ "name": "jungle", "columns": 10, "tilecount": 30 }"#.to_owned()),
+        )
+        .unwrap_err();
+
+        assert_eq!(err, "layer 'ground' is base64 encoded, export it as CSV");
+    }
+}